@@ -0,0 +1,211 @@
+use crate::renderer::{InstanceRaw, ModelRenderer, ModelVertex, Vertex};
+use crate::texture;
+
+// G-buffer-lite for custom passes : the visible surfaces of the opaque objects are drawn again
+// over their depth, writing the world normal & position of each pixel.
+//   normal : Rgba16Float, xyz normalized, w is 1 where a surface was drawn
+//   position : Rgba32Float, not filterable, to be read with `textureLoad`
+// Morph targets are not applied, the base geometry is drawn.
+
+pub const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+pub const POSITION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+pub struct AuxBuffers {
+    pub normal: texture::Texture,
+    pub position: texture::Texture,
+    pipeline: wgpu::RenderPipeline,
+    instanced_pipeline: wgpu::RenderPipeline,
+}
+
+impl AuxBuffers {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_format: wgpu::TextureFormat,
+        camera_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let (normal, position) = AuxBuffers::create_targets(device, config);
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Aux Buffers Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Aux Buffers Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader/aux_buffers.wgsl").into()),
+        });
+        let pipeline = AuxBuffers::create_pipeline(
+            device,
+            &layout,
+            &shader,
+            "vs_main",
+            &[ModelVertex::desc()],
+            depth_format,
+        );
+        let instanced_pipeline = AuxBuffers::create_pipeline(
+            device,
+            &layout,
+            &shader,
+            "vs_instanced",
+            &[ModelVertex::desc(), InstanceRaw::desc()],
+            depth_format,
+        );
+
+        AuxBuffers {
+            normal,
+            position,
+            pipeline,
+            instanced_pipeline,
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> (texture::Texture, texture::Texture) {
+        (
+            texture::Texture::create_render_target_with_format(
+                device,
+                config,
+                NORMAL_FORMAT,
+                "aux_normal",
+            ),
+            texture::Texture::create_render_target_with_format(
+                device,
+                config,
+                POSITION_FORMAT,
+                "aux_position",
+            ),
+        )
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        entry_point: &str,
+        vertex_layouts: &[wgpu::VertexBufferLayout],
+        depth_format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Aux Buffers Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point,
+                buffers: vertex_layouts,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[
+                    wgpu::ColorTargetState {
+                        format: NORMAL_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    },
+                    wgpu::ColorTargetState {
+                        format: POSITION_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    },
+                ],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // the depth test already keeps the visible faces only
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                clamp_depth: false,
+                conservative: false,
+            },
+            // the depth of the opaque pass is kept, only the surfaces matching it are written
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        let (normal, position) = AuxBuffers::create_targets(device, config);
+        self.normal = normal;
+        self.position = position;
+    }
+
+    // only the objects writing their depth without offset, in front of the others, are drawn
+    fn is_drawn(renderer: &ModelRenderer) -> bool {
+        let options = renderer.render_options();
+        !options.samples_scene_color
+            && !options.wireframe
+            && options.depth_write_enabled
+            && options.depth_compare == wgpu::CompareFunction::Less
+            && options.viewport_depth.is_none()
+    }
+
+    pub fn draw<'a, 'r>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        renderers: impl Iterator<Item = &'r ModelRenderer>,
+        camera_bind_group: &wgpu::BindGroup,
+        depth_stencil_attachment: wgpu::RenderPassDepthStencilAttachment<'a>,
+    ) {
+        let clear = wgpu::Operations {
+            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+            store: true,
+        };
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Aux Buffers Pass"),
+            color_attachments: &[
+                wgpu::RenderPassColorAttachment {
+                    view: &self.normal.view,
+                    resolve_target: None,
+                    ops: clear,
+                },
+                wgpu::RenderPassColorAttachment {
+                    view: &self.position.view,
+                    resolve_target: None,
+                    ops: clear,
+                },
+            ],
+            depth_stencil_attachment: Some(depth_stencil_attachment),
+        });
+
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        for renderer in renderers.filter(|renderer| AuxBuffers::is_drawn(renderer)) {
+            match renderer.instance_buffer.as_ref() {
+                Some(instance_buffer) => {
+                    render_pass.set_pipeline(&self.instanced_pipeline);
+                    render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+                }
+                None => render_pass.set_pipeline(&self.pipeline),
+            }
+            let instances = 0..renderer.instance_count() as u32;
+            // the pipelines only draw triangle lists
+            for mesh in renderer
+                .model
+                .meshes
+                .iter()
+                .filter(|mesh| mesh.geometry.topology == wgpu::PrimitiveTopology::TriangleList)
+            {
+                render_pass.set_vertex_buffer(0, mesh.geometry.vertex_buffer.slice(..));
+                render_pass.set_index_buffer(
+                    mesh.geometry.index_buffer.slice(..),
+                    wgpu::IndexFormat::Uint32,
+                );
+                render_pass.draw_indexed(0..mesh.geometry.num_elements, 0, instances.clone());
+            }
+        }
+    }
+}
@@ -1,8 +1,9 @@
+use crate::random::{self, Random};
 use cgmath::*;
 use std::f32::consts::FRAC_PI_2;
 use std::time::Duration;
 use wgpu::util::DeviceExt;
-use winit::dpi::PhysicalPosition;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
 use winit::event::*;
 
 #[rustfmt::skip]
@@ -15,11 +16,27 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
 
 const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
 
-#[derive(Debug)]
+// vertical field of view in degrees
+pub const DEFAULT_FOV: f32 = 45.0;
+const MIN_FOV: f32 = 10.0;
+const MAX_FOV: f32 = 120.0;
+
+// world units per second moved with the keys
+pub const DEFAULT_SPEED: f32 = 4.0;
+// radians turned per pixel of mouse motion, scaled by the frame time in seconds
+pub const DEFAULT_SENSITIVITY: f32 = 0.4;
+// world units moved per pixel scrolled (a wheel line is 100 pixels), scaled by the frame time
+pub const DEFAULT_ZOOM_SPEED: f32 = DEFAULT_SPEED * DEFAULT_SENSITIVITY;
+
+#[derive(Debug, Clone)]
 pub struct CameraParameters {
     pub position: Point3<f32>,
     yaw: Rad<f32>,
     pitch: Rad<f32>,
+    // rotation around the view direction
+    roll: Rad<f32>,
+    // world up, yaw & pitch are relative to it
+    up: Vector3<f32>,
 }
 
 impl CameraParameters {
@@ -32,21 +49,58 @@ impl CameraParameters {
             position: position.into(),
             yaw: yaw.into(),
             pitch: pitch.into(),
+            roll: Rad(0.0),
+            up: Vector3::unit_y(),
+        }
+    }
+
+    // rotation from the default Y up to the camera up
+    fn up_rotation(&self) -> Quaternion<f32> {
+        Quaternion::from_arc(Vector3::unit_y(), self.up, Some(Vector3::unit_x()))
+    }
+
+    fn direction(&self) -> Vector3<f32> {
+        self.up_rotation()
+            * Vector3::new(self.yaw.0.cos(), self.pitch.0.sin(), self.yaw.0.sin()).normalize()
+    }
+
+    // yaw & pitch giving the direction from the position to `target`
+    fn look_at(&mut self, target: Point3<f32>) {
+        let direction = self.up_rotation().invert() * (target - self.position);
+        let horizontal = (direction.x * direction.x + direction.z * direction.z).sqrt();
+        if horizontal <= f32::EPSILON && direction.y.abs() <= f32::EPSILON {
+            return;
+        }
+        // straight above or below the yaw is undefined, the current one is kept
+        if horizontal > f32::EPSILON {
+            self.yaw = Rad(direction.z.atan2(direction.x));
         }
+        // the horizontal part of `direction()` has a unit length
+        let pitch = (direction.y / horizontal.max(f32::EPSILON))
+            .clamp(-1.0, 1.0)
+            .asin();
+        self.pitch = Rad(pitch.clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2));
     }
 
     pub fn calc_matrix(&self) -> Matrix4<f32> {
-        Matrix4::look_to_rh(
-            self.position,
-            Vector3::new(self.yaw.0.cos(), self.pitch.0.sin(), self.yaw.0.sin()).normalize(),
-            Vector3::unit_y(),
-        )
+        let direction = self.direction();
+        let up = Quaternion::from_axis_angle(direction, self.roll) * self.up;
+        Matrix4::look_to_rh(self.position, direction, up)
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProjectionKind {
+    Perspective,
+    Orthographic,
+}
+
 pub struct Projection {
+    kind: ProjectionKind,
     aspect: f32,
     fovy: Rad<f32>,
+    // half of the visible height in orthographic mode
+    ortho_height: f32,
     znear: f32,
     zfar: f32,
 }
@@ -54,8 +108,10 @@ pub struct Projection {
 impl Projection {
     pub fn new<F: Into<Rad<f32>>>(width: u32, height: u32, fovy: F, znear: f32, zfar: f32) -> Self {
         Self {
+            kind: ProjectionKind::Perspective,
             aspect: width as f32 / height as f32,
             fovy: fovy.into(),
+            ortho_height: 1.0,
             znear,
             zfar,
         }
@@ -65,8 +121,97 @@ impl Projection {
         self.aspect = width as f32 / height as f32;
     }
 
+    pub fn kind(&self) -> ProjectionKind {
+        self.kind
+    }
+
+    pub fn fovy(&self) -> Rad<f32> {
+        self.fovy
+    }
+
+    pub fn set_fovy<F: Into<Rad<f32>>>(&mut self, fovy: F) {
+        self.fovy = fovy.into();
+    }
+
+    // half of the visible height in orthographic mode
+    pub fn set_ortho_height(&mut self, ortho_height: f32) {
+        self.ortho_height = ortho_height;
+    }
+
+    // distances of the near and far clipping planes
+    pub fn depth_range(&self) -> (f32, f32) {
+        (self.znear, self.zfar)
+    }
+
+    // switch the projection, keeping objects at `focus_distance` at the same apparent scale
+    pub fn set_kind(&mut self, kind: ProjectionKind, focus_distance: f32) {
+        if kind == ProjectionKind::Orthographic && self.kind == ProjectionKind::Perspective {
+            self.ortho_height = focus_distance * (self.fovy / 2.0).tan();
+        }
+        self.kind = kind;
+    }
+
+    // distance at which a sphere of `radius` fills the view
+    fn framing_distance(&self, radius: f32) -> f32 {
+        let half_fovy = self.fovy.0 / 2.0;
+        let half_fovx = (half_fovy.tan() * self.aspect).atan();
+        radius / half_fovy.min(half_fovx).sin()
+    }
+
     pub fn calc_matrix(&self) -> Matrix4<f32> {
-        OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, self.znear, self.zfar)
+        match self.kind {
+            ProjectionKind::Perspective => {
+                OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, self.znear, self.zfar)
+            }
+            ProjectionKind::Orthographic => {
+                let half_width = self.ortho_height * self.aspect;
+                OPENGL_TO_WGPU_MATRIX
+                    * ortho(
+                        -half_width,
+                        half_width,
+                        -self.ortho_height,
+                        self.ortho_height,
+                        self.znear,
+                        self.zfar,
+                    )
+            }
+        }
+    }
+}
+
+// input of one frame driving the camera without winit events, e.g. from another input
+// system, the network or a replay. It is consumed by the next update
+#[derive(Debug, Default, Copy, Clone)]
+pub struct CameraInput {
+    // right, up & forward amounts in [-1, 1] added to the held keys, scaled by the speed
+    pub move_dir: [f32; 3],
+    // horizontal & vertical look in pixels of mouse motion, scaled by the sensitivity
+    pub look_delta: [f32; 2],
+    // pixels scrolled, positive moves backward, scaled by the zoom speed
+    pub scroll: f32,
+}
+
+// part of the view eased by the smoothing
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct SmoothedView {
+    position: Point3<f32>,
+    yaw: Rad<f32>,
+    pitch: Rad<f32>,
+}
+
+impl SmoothedView {
+    fn of(camera: &CameraParameters) -> Self {
+        Self {
+            position: camera.position,
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+        }
+    }
+
+    fn apply_to(&self, camera: &mut CameraParameters) {
+        camera.position = self.position;
+        camera.yaw = self.yaw;
+        camera.pitch = self.pitch;
     }
 }
 
@@ -78,15 +223,36 @@ pub struct CameraController {
     amount_backward: f32,
     amount_up: f32,
     amount_down: f32,
-    rotate_horizontal: f32,
-    rotate_vertical: f32,
-    scroll: f32,
+    amount_roll_left: f32,
+    amount_roll_right: f32,
+    // roll with Q & E keys, disabled by default
+    roll_enabled: bool,
+    // button held to look around
+    look_button: MouseButton,
+    // input of the mouse & of `apply_input` until the next update
+    input: CameraInput,
     speed: f32,
     sensitivity: f32,
+    zoom_speed: f32,
+    // seconds for the camera to cover 63% of the way to where the input moved it, 0 to
+    // move it right away
+    smoothing: f32,
+    // range of distances to `zoom_target` the scroll keeps the camera in, unbounded by default
+    zoom_target: Point3<f32>,
+    min_distance: f32,
+    max_distance: f32,
+    // view the input leads to & the one eased toward it on the last update
+    smoothed: Option<(SmoothedView, SmoothedView)>,
 }
 
 impl CameraController {
+    // the zoom speed is `speed * sensitivity`, as before it could be set on its own
     pub fn new(speed: f32, sensitivity: f32) -> Self {
+        Self::with_zoom_speed(speed, sensitivity, speed * sensitivity)
+    }
+
+    // see `DEFAULT_SPEED`, `DEFAULT_SENSITIVITY` & `DEFAULT_ZOOM_SPEED` for the units
+    pub fn with_zoom_speed(speed: f32, sensitivity: f32, zoom_speed: f32) -> Self {
         Self {
             amount_left: 0.0,
             amount_right: 0.0,
@@ -94,14 +260,88 @@ impl CameraController {
             amount_backward: 0.0,
             amount_up: 0.0,
             amount_down: 0.0,
-            rotate_horizontal: 0.0,
-            rotate_vertical: 0.0,
-            scroll: 0.0,
+            amount_roll_left: 0.0,
+            amount_roll_right: 0.0,
+            roll_enabled: false,
+            look_button: MouseButton::Left,
+            input: CameraInput::default(),
             speed,
             sensitivity,
+            zoom_speed,
+            smoothing: 0.0,
+            zoom_target: Point3::origin(),
+            min_distance: 0.0,
+            max_distance: f32::INFINITY,
+            smoothed: None,
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.sensitivity = sensitivity;
+    }
+
+    pub fn sensitivity(&self) -> f32 {
+        self.sensitivity
+    }
+
+    pub fn set_zoom_speed(&mut self, zoom_speed: f32) {
+        self.zoom_speed = zoom_speed;
+    }
+
+    pub fn zoom_speed(&self) -> f32 {
+        self.zoom_speed
+    }
+
+    // ease the position, yaw & pitch toward the input over about `smoothing` seconds, 0
+    // disables it. The easing is exponential so it never goes past the input, e.g. 0.05
+    // still follows fast flicks closely
+    pub fn set_smoothing(&mut self, smoothing: f32) {
+        self.smoothing = smoothing.max(0.0);
+        if self.smoothing == 0.0 {
+            self.smoothed = None;
         }
     }
 
+    pub fn smoothing(&self) -> f32 {
+        self.smoothing
+    }
+
+    // point the zoom distances are measured from, the world origin by default
+    pub fn set_zoom_target<V: Into<Point3<f32>>>(&mut self, target: V) {
+        self.zoom_target = target.into();
+    }
+
+    pub fn zoom_target(&self) -> Point3<f32> {
+        self.zoom_target
+    }
+
+    // closest the scroll brings the camera to the zoom target, e.g. the radius of a model
+    // to keep the camera out of it
+    pub fn set_min_distance(&mut self, distance: f32) {
+        self.min_distance = distance.max(0.0);
+    }
+
+    pub fn min_distance(&self) -> f32 {
+        self.min_distance
+    }
+
+    // farthest the scroll takes the camera from the zoom target
+    pub fn set_max_distance(&mut self, distance: f32) {
+        self.max_distance = distance.max(0.0);
+    }
+
+    pub fn max_distance(&self) -> f32 {
+        self.max_distance
+    }
+
     pub fn process_keyboard(&mut self, key: VirtualKeyCode, state: ElementState) -> bool {
         let amount = if state == ElementState::Pressed {
             1.0
@@ -133,56 +373,134 @@ impl CameraController {
                 self.amount_down = amount;
                 true
             }
+            VirtualKeyCode::Q if self.roll_enabled => {
+                self.amount_roll_left = amount;
+                true
+            }
+            VirtualKeyCode::E if self.roll_enabled => {
+                self.amount_roll_right = amount;
+                true
+            }
             _ => false,
         }
     }
 
+    // device button ids follow the X11 numbering : 1 left, 2 middle, 3 right
+    pub fn is_look_button(&self, button: ButtonId) -> bool {
+        let look_button = match self.look_button {
+            MouseButton::Left => 1,
+            MouseButton::Middle => 2,
+            MouseButton::Right => 3,
+            MouseButton::Other(id) => id as ButtonId,
+        };
+        button == look_button
+    }
+
+    // replace the input of the next update, the held keys are kept
+    pub fn apply_input(&mut self, input: CameraInput) {
+        self.input = input;
+    }
+
     pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
-        self.rotate_horizontal = mouse_dx as f32;
-        self.rotate_vertical = mouse_dy as f32;
+        self.apply_input(CameraInput {
+            look_delta: [mouse_dx as f32, mouse_dy as f32],
+            ..self.input
+        });
     }
 
     pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
-        self.scroll = -match delta {
+        let scroll = -match delta {
             // I'm assuming a line is about 100 pixels
             MouseScrollDelta::LineDelta(_, scroll) => scroll * 100.0,
             MouseScrollDelta::PixelDelta(PhysicalPosition { y: scroll, .. }) => *scroll as f32,
         };
+        self.apply_input(CameraInput {
+            scroll,
+            ..self.input
+        });
     }
 
     pub fn update_camera(&mut self, camera: &mut CameraParameters, dt: Duration) {
+        if self.smoothing <= 0.0 {
+            self.move_camera(camera, dt);
+            return;
+        }
+        // the input keeps moving the previous target, unless the camera was placed
+        // elsewhere since, e.g. by `look_at` or `reset`
+        let mut target = camera.clone();
+        if let Some((previous_target, eased)) = self.smoothed {
+            if eased == SmoothedView::of(camera) {
+                previous_target.apply_to(&mut target);
+            }
+        }
+        self.move_camera(&mut target, dt);
+
+        // frame rate independent, a long frame gets closer to the target but not past it
+        let t = 1.0 - (-dt.as_secs_f32() / self.smoothing).exp();
+        camera.position += (target.position - camera.position) * t;
+        camera.yaw += (target.yaw - camera.yaw) * t;
+        camera.pitch += (target.pitch - camera.pitch) * t;
+        camera.roll = target.roll;
+        self.smoothed = Some((SmoothedView::of(&target), SmoothedView::of(camera)));
+    }
+
+    // back on the sphere of the nearest allowed distance, `fallback` is the direction used
+    // when the camera is right on the zoom target
+    fn clamp_zoom(&self, camera: &mut CameraParameters, fallback: Vector3<f32>) {
+        let offset = camera.position - self.zoom_target;
+        let distance = offset.magnitude();
+        let clamped = distance.clamp(self.min_distance, self.max_distance.max(self.min_distance));
+        if clamped == distance {
+            return;
+        }
+        let direction = if distance > f32::EPSILON {
+            offset / distance
+        } else {
+            fallback
+        };
+        camera.position = self.zoom_target + direction * clamped;
+    }
+
+    fn move_camera(&mut self, camera: &mut CameraParameters, dt: Duration) {
         let dt = dt.as_secs_f32();
+        let input = std::mem::take(&mut self.input);
+        let [move_right, move_up, move_forward] =
+            input.move_dir.map(|amount| amount.clamp(-1.0, 1.0));
+
+        // Movements are relative to the camera up
+        let up_rotation = camera.up_rotation();
 
         // Move forward/backward and left/right
         let (yaw_sin, yaw_cos) = camera.yaw.0.sin_cos();
-        let forward = Vector3::new(yaw_cos, 0.0, yaw_sin).normalize();
-        let right = Vector3::new(-yaw_sin, 0.0, yaw_cos).normalize();
-        camera.position += forward * (self.amount_forward - self.amount_backward) * self.speed * dt;
-        camera.position += right * (self.amount_right - self.amount_left) * self.speed * dt;
+        let forward = up_rotation * Vector3::new(yaw_cos, 0.0, yaw_sin).normalize();
+        let right = up_rotation * Vector3::new(-yaw_sin, 0.0, yaw_cos).normalize();
+        camera.position +=
+            forward * (self.amount_forward - self.amount_backward + move_forward) * self.speed * dt;
+        camera.position +=
+            right * (self.amount_right - self.amount_left + move_right) * self.speed * dt;
 
         // Move in/out (aka. "zoom")
         // Note: this isn't an actual zoom. The camera's position
         // changes when zooming. I've added this to make it easier
         // to get closer to an object you want to focus on.
         let (pitch_sin, pitch_cos) = camera.pitch.0.sin_cos();
-        let scrollward =
-            Vector3::new(pitch_cos * yaw_cos, pitch_sin, pitch_cos * yaw_sin).normalize();
-        camera.position += scrollward * self.scroll * self.speed * self.sensitivity * dt;
-        self.scroll = 0.0;
-
-        // Move up/down. Since we don't use roll, we can just
-        // modify the y coordinate directly.
-        camera.position.y += (self.amount_up - self.amount_down) * self.speed * dt;
+        let scrollward = up_rotation
+            * Vector3::new(pitch_cos * yaw_cos, pitch_sin, pitch_cos * yaw_sin).normalize();
+        if input.scroll != 0.0 {
+            camera.position += scrollward * input.scroll * self.zoom_speed * dt;
+            self.clamp_zoom(camera, -scrollward);
+        }
 
-        // Rotate
-        camera.yaw += Rad(self.rotate_horizontal) * self.sensitivity * dt;
-        camera.pitch += Rad(-self.rotate_vertical) * self.sensitivity * dt;
+        // Move up/down along the camera up
+        camera.position +=
+            camera.up * (self.amount_up - self.amount_down + move_up) * self.speed * dt;
 
-        // If process_mouse isn't called every frame, these values
-        // will not get set to zero, and the camera will rotate
-        // when moving in a non cardinal direction.
-        self.rotate_horizontal = 0.0;
-        self.rotate_vertical = 0.0;
+        // Rotate, the input was taken so the camera doesn't keep rotating
+        // when process_mouse isn't called every frame
+        camera.yaw += Rad(input.look_delta[0]) * self.sensitivity * dt;
+        camera.pitch += Rad(-input.look_delta[1]) * self.sensitivity * dt;
+        // roll at one radian per second
+        camera.roll += Rad(self.amount_roll_right - self.amount_roll_left) * dt;
 
         // Keep the camera's angle from going too high/low.
         if camera.pitch < -Rad(SAFE_FRAC_PI_2) {
@@ -201,6 +519,10 @@ pub struct CameraUniform {
     // We can't use cgmath with bytemuck directly so we'll have
     // to convert the Matrix4 into a 4x4 f32 array
     view_proj: [[f32; 4]; 4],
+    // seconds since the scene started, for animated shaders
+    time: f32,
+    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
+    _padding: [u32; 3],
 }
 
 impl CameraUniform {
@@ -208,6 +530,8 @@ impl CameraUniform {
         Self {
             view_position: [0.0; 4],
             view_proj: cgmath::Matrix4::identity().into(),
+            time: 0.0,
+            _padding: [0; 3],
         }
     }
 
@@ -215,13 +539,65 @@ impl CameraUniform {
         self.view_position = camera.position.to_homogeneous().into();
         self.view_proj = (projection.calc_matrix() * camera.calc_matrix()).into();
     }
+
+    // offset the projection by a sub-pixel amount, in clip space
+    fn jitter(&mut self, jitter: [f32; 2]) {
+        let offset = Matrix4::from_translation(Vector3::new(jitter[0], jitter[1], 0.0));
+        self.view_proj = (offset * Matrix4::from(self.view_proj)).into();
+    }
+}
+
+// rotation in radians of a shake of intensity 1, the offset is in world units
+const SHAKE_ANGLE: f32 = 0.05;
+
+// decaying random offsets applied over the camera without moving it
+#[derive(Debug)]
+struct Shake {
+    intensity: f32,
+    duration: Duration,
+    remaining: Duration,
+    // offsets of the current frame, drawn on each update
+    offset: Vector3<f32>,
+    angles: [f32; 3],
+}
+
+impl Shake {
+    fn advance(&mut self, dt: Duration, random: &mut Random) {
+        self.remaining = self.remaining.saturating_sub(dt);
+        let decay = self.remaining.as_secs_f32() / self.duration.as_secs_f32().max(f32::EPSILON);
+        let amplitude = self.intensity * decay * decay;
+        self.offset = Vector3::new(
+            random.next_signed(),
+            random.next_signed(),
+            random.next_signed(),
+        ) * amplitude;
+        for angle in self.angles.iter_mut() {
+            *angle = random.next_signed() * amplitude * SHAKE_ANGLE;
+        }
+    }
+
+    fn apply(&self, camera: &CameraParameters) -> CameraParameters {
+        let mut shaken = camera.clone();
+        shaken.position += self.offset;
+        shaken.yaw += Rad(self.angles[0]);
+        shaken.pitch += Rad(self.angles[1]);
+        shaken.roll += Rad(self.angles[2]);
+        shaken
+    }
 }
 
 pub struct Camera {
     camera_parameters: CameraParameters,
+    // parameters restored by `reset`
+    initial_parameters: CameraParameters,
     pub projection: Projection,
     pub camera_controller: CameraController,
     camera_uniform: CameraUniform,
+    // sub-pixel offset of the projection used by the temporal anti-aliasing
+    jitter: [f32; 2],
+    shake: Option<Shake>,
+    // draws the shake offsets, a new shake keeps drawing from the same sequence
+    random: Random,
     camera_buffer: wgpu::Buffer,
     pub bind_group_layout: wgpu::BindGroupLayout,
     pub bind_group: wgpu::BindGroup,
@@ -231,9 +607,14 @@ impl Camera {
     pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
         let camera_parameters =
             CameraParameters::new((0.0, 5.0, 10.0), cgmath::Deg(-90.0), cgmath::Deg(-20.0));
-        let projection =
-            Projection::new(config.width, config.height, cgmath::Deg(45.0), 0.1, 100.0);
-        let camera_controller = CameraController::new(4.0, 0.4);
+        let projection = Projection::new(
+            config.width,
+            config.height,
+            cgmath::Deg(DEFAULT_FOV),
+            0.1,
+            100.0,
+        );
+        let camera_controller = CameraController::new(DEFAULT_SPEED, DEFAULT_SENSITIVITY);
         let mut camera_uniform = CameraUniform::new();
         camera_uniform.update_view_proj(&camera_parameters, &projection);
 
@@ -267,21 +648,192 @@ impl Camera {
         });
 
         Camera {
+            initial_parameters: camera_parameters.clone(),
             camera_parameters,
             projection,
             camera_controller,
             camera_uniform,
+            jitter: [0.0; 2],
+            shake: None,
+            random: Random::new(random::DEFAULT_SEED),
             camera_buffer,
             bind_group_layout,
             bind_group,
         }
     }
 
+    pub fn position(&self) -> Point3<f32> {
+        self.camera_parameters.position
+    }
+
+    // the matrix is uploaded on the next update
+    pub fn set_position<V: Into<Point3<f32>>>(&mut self, position: V) {
+        self.camera_parameters.position = position.into();
+    }
+
+    // place the camera at `eye` looking at `target`, `reset` returns to this view
+    pub fn set_origin<V: Into<Point3<f32>>>(&mut self, eye: V, target: V) {
+        self.camera_parameters.position = eye.into();
+        self.camera_parameters.look_at(target.into());
+        self.initial_parameters = self.camera_parameters.clone();
+        // the matrix is uploaded on the next update
+        self.camera_uniform
+            .update_view_proj(&self.camera_parameters, &self.projection);
+    }
+
+    // turn the camera toward `target` from its current position, the pitch stays
+    // just shy of 90 degrees when the target is straight above or below
+    pub fn look_at<V: Into<Point3<f32>>>(&mut self, target: V) {
+        self.camera_parameters.look_at(target.into());
+    }
+
+    // back to the view the camera was created with, or the one given to `set_origin`
+    pub fn reset(&mut self) {
+        self.camera_parameters = self.initial_parameters.clone();
+        // the matrix is uploaded on the next update
+        self.camera_uniform
+            .update_view_proj(&self.camera_parameters, &self.projection);
+    }
+
+    // move back along the view direction until the sphere fills the view
+    pub fn frame<V: Into<Point3<f32>>>(&mut self, center: V, radius: f32) {
+        let distance = self.projection.framing_distance(radius);
+        self.camera_parameters.position =
+            center.into() - self.camera_parameters.direction() * distance;
+        if self.projection.kind() == ProjectionKind::Orthographic {
+            self.projection.ortho_height = radius;
+        }
+        // the matrix is uploaded on the next update
+        self.camera_uniform
+            .update_view_proj(&self.camera_parameters, &self.projection);
+    }
+
+    // drive the camera without winit events, see `CameraInput`
+    pub fn apply_input(&mut self, input: CameraInput) {
+        self.camera_controller.apply_input(input);
+    }
+
+    // seconds exposed to the shaders as `camera.time`, applied on the next update
+    pub fn set_time(&mut self, seconds: f32) {
+        self.camera_uniform.time = seconds;
+    }
+
+    // applied on the next update
+    pub fn set_jitter(&mut self, jitter: [f32; 2]) {
+        self.jitter = jitter;
+    }
+
+    // world to view space, with the shake
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        self.view_parameters().calc_matrix()
+    }
+
+    // view projection without jitter
+    pub fn view_proj(&self) -> Matrix4<f32> {
+        self.projection.calc_matrix() * self.view_matrix()
+    }
+
+    // ray through a pixel of a viewport of `viewport` pixels, from the near plane & with a
+    // normalized direction, e.g. to pick the object under the cursor
+    pub fn screen_ray(
+        &self,
+        pos: PhysicalPosition<f64>,
+        viewport: PhysicalSize<u32>,
+    ) -> (Point3<f32>, Vector3<f32>) {
+        let x = (2.0 * pos.x / viewport.width.max(1) as f64 - 1.0) as f32;
+        let y = (1.0 - 2.0 * pos.y / viewport.height.max(1) as f64) as f32;
+        let inverse = self.view_proj().invert().unwrap_or_else(Matrix4::identity);
+        let near = inverse.transform_point(Point3::new(x, y, 0.0));
+        let far = inverse.transform_point(Point3::new(x, y, 1.0));
+        (near, (far - near).normalize())
+    }
+
+    // shake the view for `duration` seconds, `intensity` is the initial offset in world units,
+    // the position and orientation of the camera are left untouched
+    pub fn shake(&mut self, intensity: f32, duration: f32) {
+        let duration = Duration::from_secs_f32(duration.max(0.0));
+        self.shake = Some(Shake {
+            intensity,
+            duration,
+            remaining: duration,
+            offset: Vector3::zero(),
+            angles: [0.0; 3],
+        });
+    }
+
+    // restart the sequence of the shake offsets, the same seed gives the same shakes
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.random = Random::new(seed);
+    }
+
+    // parameters the view is rendered from, shaken if a shake is running
+    fn view_parameters(&self) -> CameraParameters {
+        match self.shake.as_ref() {
+            Some(shake) => shake.apply(&self.camera_parameters),
+            None => self.camera_parameters.clone(),
+        }
+    }
+
+    // vertical field of view in degrees, clamped between 10 and 120
+    pub fn set_fov(&mut self, degrees: f32) {
+        self.projection
+            .set_fovy(Deg(degrees.clamp(MIN_FOV, MAX_FOV)));
+        // the matrix is uploaded on the next update
+        self.camera_uniform
+            .update_view_proj(&self.camera_parameters, &self.projection);
+    }
+
+    pub fn fov(&self) -> f32 {
+        Deg::from(self.projection.fovy()).0
+    }
+
+    pub fn set_up(&mut self, up: Vector3<f32>) {
+        self.camera_parameters.up = up.normalize();
+    }
+
+    // rotate the camera around its view direction
+    pub fn roll(&mut self, angle: f32) {
+        self.camera_parameters.roll += Rad(angle);
+    }
+
+    pub fn set_look_button(&mut self, button: MouseButton) {
+        self.camera_controller.look_button = button;
+    }
+
+    // allow the free-fly controller to roll with Q & E
+    pub fn set_roll_enabled(&mut self, enabled: bool) {
+        self.camera_controller.roll_enabled = enabled;
+    }
+
+    pub fn set_projection_kind(&mut self, kind: ProjectionKind) {
+        // the orthographic scale matches the perspective one at the world origin
+        let focus_distance = self.camera_parameters.position.to_vec().magnitude();
+        self.projection.set_kind(kind, focus_distance);
+        // the matrix is uploaded on the next update
+        self.camera_uniform
+            .update_view_proj(&self.camera_parameters, &self.projection);
+    }
+
+    pub fn toggle_projection(&mut self) {
+        match self.projection.kind() {
+            ProjectionKind::Perspective => self.set_projection_kind(ProjectionKind::Orthographic),
+            ProjectionKind::Orthographic => self.set_projection_kind(ProjectionKind::Perspective),
+        }
+    }
+
     pub fn update(&mut self, queue: &wgpu::Queue, dt: std::time::Duration) {
         self.camera_controller
             .update_camera(&mut self.camera_parameters, dt);
+        if let Some(shake) = self.shake.as_mut() {
+            shake.advance(dt, &mut self.random);
+            if shake.remaining.is_zero() {
+                self.shake = None;
+            }
+        }
+        let view_parameters = self.view_parameters();
         self.camera_uniform
-            .update_view_proj(&self.camera_parameters, &self.projection);
+            .update_view_proj(&view_parameters, &self.projection);
+        self.camera_uniform.jitter(self.jitter);
         queue.write_buffer(
             &self.camera_buffer,
             0,
@@ -0,0 +1,110 @@
+use crate::{camera, fullscreen};
+use wgpu::util::DeviceExt;
+
+// Debug view of the depth buffer : the depth is linearized with the near & far planes
+// of the camera and drawn as grayscale in place of the scene color.
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DepthDebugUniform {
+    near: f32,
+    far: f32,
+    orthographic: f32,
+    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
+    _padding: u32,
+}
+
+pub struct DepthDebug {
+    pass: fullscreen::FullscreenPass,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    bind_group: wgpu::BindGroup,
+}
+
+impl DepthDebug {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_view: &wgpu::TextureView,
+    ) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Depth Debug Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[DepthDebugUniform {
+                near: 0.1,
+                far: 100.0,
+                orthographic: 0.0,
+                _padding: 0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("depth_debug_uniform_bind_group_layout"),
+        });
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &uniform_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("depth_debug_uniform_bind_group"),
+        });
+
+        let pass = fullscreen::FullscreenPass::with_layouts(
+            device,
+            include_str!("shader/depth_debug.wgsl"),
+            &[wgpu::TextureSampleType::Depth],
+            &[&uniform_layout],
+            config.format,
+        );
+        let bind_group = pass.create_bind_group(device, &[depth_view]);
+
+        DepthDebug {
+            pass,
+            uniform_buffer,
+            uniform_bind_group,
+            bind_group,
+        }
+    }
+
+    // the depth texture is recreated on resize
+    pub fn resize(&mut self, device: &wgpu::Device, depth_view: &wgpu::TextureView) {
+        self.bind_group = self.pass.create_bind_group(device, &[depth_view]);
+    }
+
+    pub fn draw(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        projection: &camera::Projection,
+    ) {
+        let (near, far) = projection.depth_range();
+        let orthographic = projection.kind() == camera::ProjectionKind::Orthographic;
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[DepthDebugUniform {
+                near,
+                far,
+                orthographic: if orthographic { 1.0 } else { 0.0 },
+                _padding: 0,
+            }]),
+        );
+
+        self.pass.draw_with_bind_groups(
+            encoder,
+            target,
+            &[&self.bind_group, &self.uniform_bind_group],
+        );
+    }
+}
@@ -0,0 +1,196 @@
+// Fullscreen pass for post effects : a single triangle covering the screen,
+// without vertex buffer, shaded by a user fragment shader.
+//
+// The fragment shader is appended to `shader/fullscreen.wgsl`, its entry point is
+// `fs_main(vertex: VertexOutput)` with `vertex.tex_coords` in [0, 1].
+// Inputs are bound in group 0 : the sampler at binding 0 then the textures from binding 1,
+// passes needing uniforms can add bind group layouts after it.
+
+pub struct FullscreenPass {
+    render_pipeline: wgpu::RenderPipeline,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+impl FullscreenPass {
+    pub fn new(
+        device: &wgpu::Device,
+        fragment_shader: &str,
+        inputs: &[wgpu::TextureSampleType],
+        target_format: wgpu::TextureFormat,
+    ) -> Self {
+        FullscreenPass::with_layouts(device, fragment_shader, inputs, &[], target_format)
+    }
+
+    // `extra_layouts` are bound from group 1
+    pub fn with_layouts(
+        device: &wgpu::Device,
+        fragment_shader: &str,
+        inputs: &[wgpu::TextureSampleType],
+        extra_layouts: &[&wgpu::BindGroupLayout],
+        target_format: wgpu::TextureFormat,
+    ) -> Self {
+        // depth or integer textures can't be filtered
+        let filtering = inputs
+            .iter()
+            .all(|input| matches!(input, wgpu::TextureSampleType::Float { filterable: true }));
+
+        let mut entries = vec![wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler {
+                comparison: false,
+                filtering,
+            },
+            count: None,
+        }];
+        for (index, sample_type) in inputs.iter().enumerate() {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: index as u32 + 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: *sample_type,
+                },
+                count: None,
+            });
+        }
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &entries,
+            label: Some("fullscreen_bind_group_layout"),
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: if filtering {
+                wgpu::FilterMode::Linear
+            } else {
+                wgpu::FilterMode::Nearest
+            },
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let mut bind_group_layouts = vec![&bind_group_layout];
+        bind_group_layouts.extend_from_slice(extra_layouts);
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Fullscreen Pipeline Layout"),
+                bind_group_layouts: &bind_group_layouts,
+                push_constant_ranges: &[],
+            });
+        let source = format!(
+            "{}\n{}",
+            include_str!("shader/fullscreen.wgsl"),
+            fragment_shader
+        );
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Fullscreen Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Fullscreen Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format: target_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                clamp_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        });
+
+        FullscreenPass {
+            render_pipeline,
+            bind_group_layout,
+            sampler,
+        }
+    }
+
+    // bind the input textures in the order given to `new`
+    pub fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        inputs: &[&wgpu::TextureView],
+    ) -> wgpu::BindGroup {
+        let mut entries = vec![wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Sampler(&self.sampler),
+        }];
+        for (index, view) in inputs.iter().enumerate() {
+            entries.push(wgpu::BindGroupEntry {
+                binding: index as u32 + 1,
+                resource: wgpu::BindingResource::TextureView(view),
+            });
+        }
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &entries,
+            label: Some("fullscreen_bind_group"),
+        })
+    }
+
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        bind_group: &wgpu::BindGroup,
+    ) {
+        self.draw_with_bind_groups(encoder, target, &[bind_group]);
+    }
+
+    // the input bind group first, then the ones of the extra layouts
+    pub fn draw_with_bind_groups(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        bind_groups: &[&wgpu::BindGroup],
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Fullscreen Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        for (index, bind_group) in bind_groups.iter().enumerate() {
+            render_pass.set_bind_group(index as u32, bind_group, &[]);
+        }
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,279 @@
+use crate::renderer::{ModelRenderer, PRIMITIVE_RESTART};
+use gltf::json;
+use json::validation::Checked::Valid;
+
+// Binary glTF of the objects of a scene : one glTF mesh per mesh of a model, drawn by one
+// node per instance. The geometry is read from the CPU copy kept by each mesh, the diffuse
+// image of each material is embedded as its base color texture. Morph targets and the strips
+// with primitive restarts, unknown to glTF, are not exported.
+
+#[derive(Default)]
+struct GlbBuilder {
+    binary: Vec<u8>,
+    buffer_views: Vec<json::buffer::View>,
+    accessors: Vec<json::Accessor>,
+}
+
+impl GlbBuilder {
+    // index of the view of `data`, the binary stays aligned to 4 bytes for the next one
+    fn push_view(
+        &mut self,
+        data: &[u8],
+        target: Option<json::buffer::Target>,
+    ) -> json::Index<json::buffer::View> {
+        self.buffer_views.push(json::buffer::View {
+            buffer: json::Index::new(0),
+            byte_length: data.len() as u32,
+            byte_offset: Some(self.binary.len() as u32),
+            byte_stride: None,
+            name: None,
+            target: target.map(Valid),
+            extensions: None,
+            extras: Default::default(),
+        });
+        self.binary.extend_from_slice(data);
+        self.binary.resize(self.binary.len().div_ceil(4) * 4, 0);
+        json::Index::new(self.buffer_views.len() as u32 - 1)
+    }
+
+    // index of the accessor of `count` elements of `element_type` stored in `data`
+    fn push_accessor(
+        &mut self,
+        data: &[u8],
+        target: json::buffer::Target,
+        component_type: json::accessor::ComponentType,
+        element_type: json::accessor::Type,
+        count: usize,
+        bounds: Option<([f32; 3], [f32; 3])>,
+    ) -> json::Index<json::Accessor> {
+        let buffer_view = self.push_view(data, Some(target));
+        let (min, max) = match bounds {
+            Some((min, max)) => (Some(json::Value::from(&min[..])), Some(max[..].into())),
+            None => (None, None),
+        };
+        self.accessors.push(json::Accessor {
+            buffer_view: Some(buffer_view),
+            byte_offset: 0,
+            count: count as u32,
+            component_type: Valid(json::accessor::GenericComponentType(component_type)),
+            extensions: None,
+            extras: Default::default(),
+            type_: Valid(element_type),
+            min,
+            max,
+            name: None,
+            normalized: false,
+            sparse: None,
+        });
+        json::Index::new(self.accessors.len() as u32 - 1)
+    }
+}
+
+pub fn write_glb<'a, P: AsRef<std::path::Path>>(
+    renderers: impl Iterator<Item = &'a ModelRenderer>,
+    path: P,
+) -> anyhow::Result<()> {
+    let mut builder = GlbBuilder::default();
+    let mut root = json::Root::default();
+
+    for renderer in renderers {
+        let material_offset = root.materials.len();
+        for material in renderer.model.materials.iter().flatten() {
+            let mut png = Vec::new();
+            material
+                .diffuse_image
+                .write_to(&mut png, image::ImageOutputFormat::Png)?;
+            let buffer_view = builder.push_view(&png, None);
+            root.images.push(json::Image {
+                buffer_view: Some(buffer_view),
+                mime_type: Some(json::image::MimeType("image/png".to_string())),
+                name: Some(material.name.clone()),
+                uri: None,
+                extensions: None,
+                extras: Default::default(),
+            });
+            root.textures.push(json::Texture {
+                name: Some(material.name.clone()),
+                sampler: None,
+                source: json::Index::new(root.images.len() as u32 - 1),
+                extensions: None,
+                extras: Default::default(),
+            });
+            root.materials.push(json::Material {
+                name: Some(material.name.clone()),
+                pbr_metallic_roughness: json::material::PbrMetallicRoughness {
+                    base_color_texture: Some(json::texture::Info {
+                        index: json::Index::new(root.textures.len() as u32 - 1),
+                        tex_coord: 0,
+                        extensions: None,
+                        extras: Default::default(),
+                    }),
+                    metallic_factor: json::material::StrengthFactor(0.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+        }
+
+        // the model matrix of each instance, identity when the model is not instanced
+        let transforms: Vec<[[f32; 4]; 4]> = if renderer.instances.is_empty() {
+            vec![cgmath::Matrix4::from_scale(1.0).into()]
+        } else {
+            renderer
+                .instances
+                .iter()
+                .take(renderer.instance_count())
+                .map(|instance| instance.model)
+                .collect()
+        };
+
+        for mesh in renderer.model.meshes.iter() {
+            let geometry = &mesh.geometry;
+            let mode = match geometry.topology {
+                wgpu::PrimitiveTopology::PointList => json::mesh::Mode::Points,
+                wgpu::PrimitiveTopology::LineList => json::mesh::Mode::Lines,
+                wgpu::PrimitiveTopology::LineStrip => json::mesh::Mode::LineStrip,
+                wgpu::PrimitiveTopology::TriangleList => json::mesh::Mode::Triangles,
+                wgpu::PrimitiveTopology::TriangleStrip => json::mesh::Mode::TriangleStrip,
+            };
+            if geometry.indices.is_empty()
+                || (geometry.topology.is_strip() && geometry.indices.contains(&PRIMITIVE_RESTART))
+            {
+                continue;
+            }
+
+            let positions: Vec<[f32; 3]> = geometry.vertices.iter().map(|v| v.position).collect();
+            let normals: Vec<[f32; 3]> = geometry.vertices.iter().map(|v| v.normal).collect();
+            let tex_coords: Vec<[f32; 2]> =
+                geometry.vertices.iter().map(|v| v.tex_coords).collect();
+            let mut min = [f32::MAX; 3];
+            let mut max = [f32::MIN; 3];
+            for position in positions.iter() {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(position[axis]);
+                    max[axis] = max[axis].max(position[axis]);
+                }
+            }
+
+            let count = geometry.vertices.len();
+            let mut attributes = std::collections::HashMap::new();
+            attributes.insert(
+                Valid(json::mesh::Semantic::Positions),
+                builder.push_accessor(
+                    bytemuck::cast_slice(&positions),
+                    json::buffer::Target::ArrayBuffer,
+                    json::accessor::ComponentType::F32,
+                    json::accessor::Type::Vec3,
+                    count,
+                    Some((min, max)),
+                ),
+            );
+            attributes.insert(
+                Valid(json::mesh::Semantic::Normals),
+                builder.push_accessor(
+                    bytemuck::cast_slice(&normals),
+                    json::buffer::Target::ArrayBuffer,
+                    json::accessor::ComponentType::F32,
+                    json::accessor::Type::Vec3,
+                    count,
+                    None,
+                ),
+            );
+            attributes.insert(
+                Valid(json::mesh::Semantic::TexCoords(0)),
+                builder.push_accessor(
+                    bytemuck::cast_slice(&tex_coords),
+                    json::buffer::Target::ArrayBuffer,
+                    json::accessor::ComponentType::F32,
+                    json::accessor::Type::Vec2,
+                    count,
+                    None,
+                ),
+            );
+            let indices = builder.push_accessor(
+                bytemuck::cast_slice(&geometry.indices),
+                json::buffer::Target::ElementArrayBuffer,
+                json::accessor::ComponentType::U32,
+                json::accessor::Type::Scalar,
+                geometry.indices.len(),
+                None,
+            );
+
+            root.meshes.push(json::Mesh {
+                extensions: None,
+                extras: Default::default(),
+                name: Some(geometry.name.clone()),
+                primitives: vec![json::mesh::Primitive {
+                    attributes,
+                    extensions: None,
+                    extras: Default::default(),
+                    indices: Some(indices),
+                    material: mesh
+                        .material_id
+                        .map(|id| json::Index::new((material_offset + id) as u32)),
+                    mode: Valid(mode),
+                    targets: None,
+                }],
+                weights: None,
+            });
+
+            let mesh_index = json::Index::new(root.meshes.len() as u32 - 1);
+            for transform in transforms.iter() {
+                let mut matrix = [0.0; 16];
+                for (value, column) in matrix.iter_mut().zip(transform.iter().flatten()) {
+                    *value = *column;
+                }
+                root.nodes.push(json::Node {
+                    camera: None,
+                    children: None,
+                    extensions: None,
+                    extras: Default::default(),
+                    matrix: Some(matrix),
+                    mesh: Some(mesh_index),
+                    name: Some(geometry.name.clone()),
+                    rotation: None,
+                    scale: None,
+                    translation: None,
+                    skin: None,
+                    weights: None,
+                });
+            }
+        }
+    }
+
+    root.asset.generator = Some("lens".to_string());
+    if !root.nodes.is_empty() {
+        root.scenes.push(json::Scene {
+            extensions: None,
+            extras: Default::default(),
+            name: None,
+            nodes: (0..root.nodes.len() as u32).map(json::Index::new).collect(),
+        });
+        root.scene = Some(json::Index::new(0));
+    }
+    if !builder.binary.is_empty() {
+        root.buffers.push(json::Buffer {
+            byte_length: builder.binary.len() as u32,
+            name: None,
+            uri: None,
+            extensions: None,
+            extras: Default::default(),
+        });
+    }
+    root.buffer_views = builder.buffer_views;
+    root.accessors = builder.accessors;
+
+    let json = root.to_vec()?;
+    let glb = gltf::binary::Glb {
+        header: gltf::binary::Header {
+            magic: *b"glTF",
+            version: 2,
+            // computed by the writer
+            length: 0,
+        },
+        json: json.into(),
+        bin: (!builder.binary.is_empty()).then(|| builder.binary.into()),
+    };
+    glb.to_writer(std::io::BufWriter::new(std::fs::File::create(path)?))?;
+    Ok(())
+}
@@ -0,0 +1,311 @@
+use crate::object::{MaterialData, MorphTarget, Object, ObjectError};
+use crate::texture::SamplerConfig;
+use cgmath::SquareMatrix;
+use std::path::Path;
+
+// glTF & binary glTF files turned into the same models & materials as an obj file. The meshes
+// of the default scene are baked with the transforms of their nodes, each primitive becomes a
+// model. Materials keep their base color, texture times factor, & their normal map. Morph
+// targets keep their position & normal displacements, up to MAX_MORPH_TARGETS per primitive,
+// their weights start at 0 whatever the default weights of the mesh.
+// Not supported for now : skins, animations, cameras, lights, vertex colors, the
+// metallic-roughness, occlusion & emissive textures, triangle fans & line loops.
+
+// pixels of an image decoded by the gltf crate, 16 bits channels are stored native endian
+fn to_image(data: &gltf::image::Data) -> Option<image::DynamicImage> {
+    use gltf::image::Format;
+    use image::{DynamicImage, ImageBuffer};
+
+    let (width, height) = (data.width, data.height);
+    let pixels = data.pixels.clone();
+    let wide = || -> Vec<u16> {
+        data.pixels
+            .chunks_exact(2)
+            .map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]))
+            .collect()
+    };
+    Some(match data.format {
+        Format::R8 => DynamicImage::ImageLuma8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R8G8 => DynamicImage::ImageLumaA8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R8G8B8 => DynamicImage::ImageRgb8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R8G8B8A8 => DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::B8G8R8 => DynamicImage::ImageBgr8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::B8G8R8A8 => DynamicImage::ImageBgra8(ImageBuffer::from_raw(width, height, pixels)?),
+        Format::R16 => DynamicImage::ImageLuma16(ImageBuffer::from_raw(width, height, wide())?),
+        Format::R16G16 => DynamicImage::ImageLumaA16(ImageBuffer::from_raw(width, height, wide())?),
+        Format::R16G16B16 => {
+            DynamicImage::ImageRgb16(ImageBuffer::from_raw(width, height, wide())?)
+        }
+        Format::R16G16B16A16 => {
+            DynamicImage::ImageRgba16(ImageBuffer::from_raw(width, height, wide())?)
+        }
+    })
+}
+
+fn address_mode(mode: gltf::texture::WrappingMode) -> wgpu::AddressMode {
+    match mode {
+        gltf::texture::WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        gltf::texture::WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        gltf::texture::WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+    }
+}
+
+fn texture_image(
+    texture: &gltf::Texture,
+    images: &[gltf::image::Data],
+) -> Result<image::DynamicImage, ObjectError> {
+    let index = texture.source().index();
+    images
+        .get(index)
+        .and_then(to_image)
+        .ok_or_else(|| ObjectError::Io {
+            path: format!("image {}", index),
+            source: std::io::ErrorKind::InvalidData.into(),
+        })
+}
+
+fn load_material(
+    material: gltf::Material,
+    images: &[gltf::image::Data],
+) -> Result<MaterialData, ObjectError> {
+    let name = material
+        .name()
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("material {}", material.index().unwrap_or(0)));
+    let pbr = material.pbr_metallic_roughness();
+    let factor = pbr.base_color_factor();
+
+    let mut sampler = SamplerConfig::default();
+    let (diffuse, diffuse_label) = match pbr.base_color_texture() {
+        Some(info) => {
+            let texture = info.texture();
+            // one address mode per sampler, the horizontal one is kept
+            sampler.address_mode = address_mode(texture.sampler().wrap_s());
+            // mipmapped unless the sampler asks for the base level only
+            if !matches!(
+                texture.sampler().min_filter(),
+                Some(gltf::texture::MinFilter::Nearest | gltf::texture::MinFilter::Linear)
+            ) {
+                sampler.mip_level_count = SamplerConfig::FULL_MIP_CHAIN;
+            }
+            let mut diffuse = texture_image(&texture, images)?.to_rgba8();
+            if factor != [1.0; 4] {
+                for pixel in diffuse.pixels_mut() {
+                    for (channel, factor) in pixel.0.iter_mut().zip(factor) {
+                        *channel = (*channel as f32 * factor).round() as u8;
+                    }
+                }
+            }
+            (
+                image::DynamicImage::ImageRgba8(diffuse),
+                format!("{}_diffuse", name),
+            )
+        }
+        // a single pixel of the base color
+        None => {
+            let color = factor.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+            (
+                image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+                    1,
+                    1,
+                    image::Rgba(color),
+                )),
+                format!("{}_base_color", name),
+            )
+        }
+    };
+    let normal = match material.normal_texture() {
+        Some(info) => Some(texture_image(&info.texture(), images)?),
+        None => None,
+    };
+
+    Ok(MaterialData {
+        name,
+        diffuse,
+        diffuse_label,
+        sampler,
+        specular_color: [0.0; 3],
+        detail: None,
+        atlas: None,
+        normal,
+    })
+}
+
+#[derive(Default)]
+struct Meshes {
+    models: Vec<tobj::Model>,
+    topologies: Vec<wgpu::PrimitiveTopology>,
+    morph_targets: Vec<Vec<MorphTarget>>,
+}
+
+impl Meshes {
+    fn push_mesh(
+        &mut self,
+        mesh: gltf::Mesh,
+        transform: cgmath::Matrix4<f32>,
+        buffers: &[gltf::buffer::Data],
+    ) {
+        // normals are moved by the inverse transpose, to stay orthogonal to scaled surfaces
+        let normal_transform = transform
+            .invert()
+            .map(|m| {
+                cgmath::Matrix3::new(
+                    m.x.x, m.y.x, m.z.x, m.x.y, m.y.y, m.z.y, m.x.z, m.y.z, m.z.z,
+                )
+            })
+            .unwrap_or_else(cgmath::Matrix3::identity);
+
+        for primitive in mesh.primitives() {
+            let topology = match primitive.mode() {
+                gltf::mesh::Mode::Points => wgpu::PrimitiveTopology::PointList,
+                gltf::mesh::Mode::Lines => wgpu::PrimitiveTopology::LineList,
+                gltf::mesh::Mode::LineStrip => wgpu::PrimitiveTopology::LineStrip,
+                gltf::mesh::Mode::Triangles => wgpu::PrimitiveTopology::TriangleList,
+                gltf::mesh::Mode::TriangleStrip => wgpu::PrimitiveTopology::TriangleStrip,
+                mode => {
+                    log::warn!(
+                        "glTF mesh {:?} : primitive mode {:?} isn't supported, skipped",
+                        mesh.name(),
+                        mode
+                    );
+                    continue;
+                }
+            };
+
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|b| &b.0[..]));
+            let positions = match reader.read_positions() {
+                Some(positions) => positions,
+                None => continue,
+            };
+
+            let mut out = tobj::Mesh::default();
+            for position in positions {
+                let p =
+                    transform * cgmath::Vector4::new(position[0], position[1], position[2], 1.0);
+                out.positions.extend_from_slice(&[p.x, p.y, p.z]);
+            }
+            // missing normals are computed by `Model::load`
+            if let Some(normals) = reader.read_normals() {
+                for normal in normals {
+                    let n = normal_transform * cgmath::Vector3::from(normal);
+                    let n = if n == cgmath::Vector3::new(0.0, 0.0, 0.0) {
+                        n
+                    } else {
+                        cgmath::InnerSpace::normalize(n)
+                    };
+                    out.normals.extend_from_slice(&[n.x, n.y, n.z]);
+                }
+            }
+            if let Some(tex_coords) = reader.read_tex_coords(0) {
+                for tex_coord in tex_coords.into_f32() {
+                    out.texcoords.extend_from_slice(&tex_coord);
+                }
+            }
+            out.indices = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..(out.positions.len() / 3) as u32).collect(),
+            };
+            out.material_id = primitive.material().index();
+
+            // displacements are moved like the normals & the positions, without the translation
+            let vertex_count = out.positions.len() / 3;
+            let mut morph_targets = Vec::new();
+            for (positions, normals, _) in reader.read_morph_targets() {
+                if morph_targets.len() == crate::renderer::MAX_MORPH_TARGETS {
+                    log::warn!(
+                        "glTF mesh {:?} : only the first {} morph targets are kept",
+                        mesh.name(),
+                        crate::renderer::MAX_MORPH_TARGETS
+                    );
+                    break;
+                }
+                let mut target = MorphTarget {
+                    positions: match positions {
+                        Some(positions) => positions
+                            .map(|d| transform * cgmath::Vector4::new(d[0], d[1], d[2], 0.0))
+                            .map(|d| [d.x, d.y, d.z])
+                            .collect(),
+                        None => vec![[0.0; 3]; vertex_count],
+                    },
+                    normals: match normals {
+                        Some(normals) => normals
+                            .map(|d| (normal_transform * cgmath::Vector3::from(d)).into())
+                            .collect(),
+                        None => Vec::new(),
+                    },
+                };
+                // accessors of another length than the primitive are cut or padded with zeros
+                target.positions.resize(vertex_count, [0.0; 3]);
+                if !target.normals.is_empty() {
+                    target.normals.resize(vertex_count, [0.0; 3]);
+                }
+                morph_targets.push(target);
+            }
+
+            let name = match mesh.name() {
+                Some(name) => format!("{} {}", name, primitive.index()),
+                None => format!("mesh {} {}", mesh.index(), primitive.index()),
+            };
+            self.models.push(tobj::Model::new(out, name));
+            self.topologies.push(topology);
+            self.morph_targets.push(morph_targets);
+        }
+    }
+
+    fn push_node(
+        &mut self,
+        node: gltf::Node,
+        parent: cgmath::Matrix4<f32>,
+        buffers: &[gltf::buffer::Data],
+    ) {
+        let transform = parent * cgmath::Matrix4::from(node.transform().matrix());
+        if let Some(mesh) = node.mesh() {
+            self.push_mesh(mesh, transform, buffers);
+        }
+        for child in node.children() {
+            self.push_node(child, transform, buffers);
+        }
+    }
+}
+
+pub(crate) fn load(path: &Path) -> Result<Object, ObjectError> {
+    let path_label = path.to_string_lossy();
+    let (document, buffers, images) =
+        gltf::import(path).map_err(|error| ObjectError::from_gltf(&path_label, error))?;
+
+    let mut meshes = Meshes::default();
+    match document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        Some(scene) => {
+            for node in scene.nodes() {
+                meshes.push_node(node, cgmath::Matrix4::identity(), &buffers);
+            }
+        }
+        // files without scenes only hold meshes, drawn where they are defined
+        None => {
+            for mesh in document.meshes() {
+                meshes.push_mesh(mesh, cgmath::Matrix4::identity(), &buffers);
+            }
+        }
+    }
+
+    let textures = if document.materials().len() == 0 {
+        None
+    } else {
+        Some(
+            document
+                .materials()
+                .map(|material| load_material(material, &images))
+                .collect::<Result<Vec<_>, _>>()?,
+        )
+    };
+
+    Ok(Object {
+        morph_targets: meshes.morph_targets,
+        models: meshes.models,
+        topologies: meshes.topologies,
+        textures,
+    })
+}
@@ -1,136 +1,669 @@
+mod aux_buffers;
 mod camera;
+mod depth_debug;
+mod fullscreen;
+mod gltf_export;
+mod gltf_import;
 mod light;
+mod light_clusters;
+mod lines;
+mod minimap;
 mod object;
+mod overlay;
+mod picking;
+mod random;
 mod renderer;
+mod scene_color;
+mod scene_manager;
+mod shadow;
+mod skybox;
+mod taa;
+mod text;
 mod texture;
+mod water;
 
-pub use object::Object;
-pub use renderer::InstanceRaw;
-use renderer::{DrawModel, ModelRenderer};
+pub use camera::{Camera, CameraInput, ProjectionKind};
+pub use fullscreen::FullscreenPass;
+pub use light::{
+    Light, LightKind, LightUniform, LightsUniform, DIRECTIONAL_LIGHT, MAX_LIGHTS, POINT_LIGHT,
+    SPOT_LIGHT,
+};
+pub use light_clusters::{CLUSTER_COUNT, CLUSTER_LIGHTS_WGSL, DEFAULT_MAX_LIGHTS_PER_CLUSTER};
+use lines::DrawLines;
+pub use object::{LoadConfig, MaterialData, MorphTarget, Object, ObjectError};
+use overlay::DrawOverlay;
+pub use picking::Pick;
+use renderer::DrawModel;
+pub use renderer::{
+    Aabb, CustomInstances, Frustum, InstanceRaw, Material, Model, ModelError, ModelRenderer,
+    RenderOptions, RenderStats, ResourceStats, StencilMode, TargetFormats, MAX_MORPH_TARGETS,
+    MORPH_WGSL, PRIMITIVE_RESTART, TANGENT_LOCATION,
+};
+pub use scene_manager::{ObjectId, SceneManager, DEFAULT_SCENE};
+pub use shadow::ShadowMap;
+pub use texture::{SamplerConfig, SamplerPool, MAX_ANISOTROPY};
+pub use water::WaterParams;
+// key codes for the options of `Lens`, without depending on winit
+pub use winit::event::VirtualKeyCode;
 use winit::{
-    dpi::PhysicalPosition,
     event::*,
     event_loop::{ControlFlow, EventLoop},
+    window::Fullscreen,
     window::Window,
     window::WindowBuilder,
 };
 
-struct Scene {
-    surface: wgpu::Surface,
+// options used to create the scene
+struct SceneConfig {
+    line_width: f32,
+    // images drawn on top of the scene with their rect in pixels
+    overlays: Vec<(image::DynamicImage, [f32; 4])>,
+    // object framed by the camera before the first frame
+    look_at_object: Option<ObjectId>,
+    // vertical field of view of the camera in degrees
+    fov: f32,
+    // speed, sensitivity & zoom speed of the camera controller
+    camera_controls: (f32, f32, f32),
+    // eye & target of the camera before the first frame
+    camera_origin: Option<([f32; 3], [f32; 3])>,
+    // temporal anti-aliasing of the opaque objects
+    taa: bool,
+    // world normal & position of the opaque objects for custom passes
+    aux_buffers: bool,
+    // bounding boxes of the objects drawn as lines
+    show_bounds: bool,
+    // rect in pixels & height above the camera of the top-down view
+    minimap: Option<([f32; 4], f32)>,
+    // lights replacing the default rotating one
+    lights: Vec<light::LightUniform>,
+    // light added to the object color before the other lights
+    ambient: [f32; 3],
+    // lights kept in each cluster of the view
+    max_lights_per_cluster: usize,
+    // seed of the randomized effects, e.g. the camera shake
+    random_seed: u64,
+    clear_color: wgpu::Color,
+    // samples per pixel of the scene, 1 disables MSAA
+    sample_count: u32,
+    // polygon mode of the objects left to Fill
+    polygon_mode: wgpu::PolygonMode,
+    // format of the frames rendered without surface
+    headless_format: wgpu::TextureFormat,
+    // faces of the cubemap drawn behind the objects, +X, -X, +Y, -Y, +Z, -Z
+    skybox: Option<[std::path::PathBuf; 6]>,
+    // title & inner size in pixels of the window, winit picks the size when None
+    title: String,
+    size: Option<(u32, u32)>,
+    // key toggling borderless fullscreen while running
+    fullscreen_key: Option<VirtualKeyCode>,
+    // keys of the debug toggles, None disables them
+    stats_key: Option<VirtualKeyCode>,
+    depth_view_key: Option<VirtualKeyCode>,
+    bounds_key: Option<VirtualKeyCode>,
+    cursor_grab_key: Option<VirtualKeyCode>,
+    present_mode: wgpu::PresentMode,
+    // size in texels of the shadow map of the first light, no shadows when None
+    shadow_map_size: Option<u32>,
+    // constant & slope scaled depth bias of the shadow map
+    shadow_bias: (i32, f32),
+}
+
+impl Default for SceneConfig {
+    fn default() -> Self {
+        SceneConfig {
+            line_width: 2.0,
+            overlays: Vec::new(),
+            look_at_object: None,
+            fov: camera::DEFAULT_FOV,
+            camera_controls: (
+                camera::DEFAULT_SPEED,
+                camera::DEFAULT_SENSITIVITY,
+                camera::DEFAULT_ZOOM_SPEED,
+            ),
+            camera_origin: None,
+            taa: false,
+            aux_buffers: false,
+            show_bounds: false,
+            minimap: None,
+            lights: Vec::new(),
+            ambient: light::DEFAULT_AMBIENT,
+            max_lights_per_cluster: light_clusters::DEFAULT_MAX_LIGHTS_PER_CLUSTER,
+            random_seed: random::DEFAULT_SEED,
+            clear_color: DEFAULT_CLEAR_COLOR,
+            sample_count: 1,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            headless_format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            skybox: None,
+            title: "lens".to_string(),
+            size: None,
+            fullscreen_key: Some(VirtualKeyCode::F11),
+            stats_key: Some(VirtualKeyCode::F3),
+            depth_view_key: Some(VirtualKeyCode::F4),
+            bounds_key: Some(VirtualKeyCode::B),
+            cursor_grab_key: Some(VirtualKeyCode::G),
+            present_mode: wgpu::PresentMode::Fifo,
+            shadow_map_size: None,
+            shadow_bias: shadow::DEFAULT_SHADOW_BIAS,
+        }
+    }
+}
+
+pub struct Scene {
+    // None for headless scenes, their frames are read with `capture_frame`
+    surface: Option<wgpu::Surface>,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
+    depth_format: wgpu::TextureFormat,
     depth_texture: texture::Texture,
+    sample_count: u32,
+    // drawn by the passes when MSAA is enabled
+    msaa_color: Option<texture::Texture>,
     mouse_pressed: bool,
-    // camera & light binders
-    camera_binder: camera::Camera,
+    // last position of the cursor in the window, None before it moved over it
+    cursor_position: Option<winit::dpi::PhysicalPosition<f64>>,
+    // relative mouse look without holding a button, applied to the window by `Lens::run`
+    cursor_grabbed: bool,
+    // camera & light binders, the active camera renders & receives the input
+    cameras: Vec<camera::Camera>,
+    active_camera: usize,
     light_binder: light::Light,
-    // models to draw
-    // renderers for each model to draw
-    model_renderers: Vec<ModelRenderer>,
+    // depth seen from the first light, bound with the lights
+    shadow_map: shadow::ShadowMap,
+    // renderers of each named scene, only the active scene is drawn
+    scene_manager: SceneManager,
+    sampler_pool: texture::SamplerPool,
+    // offscreen target of the opaque objects
+    scene_color: scene_color::SceneColor,
+    taa: Option<taa::Taa>,
+    aux_buffers: Option<aux_buffers::AuxBuffers>,
+    // linearized depth drawn in place of the scene color, toggled by F4 by default
+    depth_debug: Option<depth_debug::DepthDebug>,
+    show_depth: bool,
+    // debug lines
+    line_renderer: lines::LineRenderer,
+    // screen-space quads drawn on top of the scene
+    overlay_renderer: overlay::OverlayRenderer,
+    // statistics of the last frame and their overlay
+    render_stats: RenderStats,
+    stats_overlay: overlay::Overlay,
+    overlays: Vec<overlay::Overlay>,
+    minimap: Option<minimap::Minimap>,
+    show_stats: bool,
+    // bounding boxes drawn each frame, toggled by B
+    show_bounds: bool,
+    clear_color: wgpu::Color,
+    skybox: Option<skybox::Skybox>,
+    stats_timer: std::time::Duration,
+    stats_frames: u32,
+    // incremented by each render
+    frame_index: u64,
+    // accumulated by each update
+    elapsed: std::time::Duration,
+}
+
+const STATS_SIZE: (u32, u32) = (256, 96);
+
+// background of the scene where no object is drawn
+const DEFAULT_CLEAR_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.1,
+    g: 0.2,
+    b: 0.3,
+    a: 1.0,
+};
+
+// channels are clamped to [0, 1]
+fn clear_color(r: f64, g: f64, b: f64, a: f64) -> wgpu::Color {
+    wgpu::Color {
+        r: r.clamp(0.0, 1.0),
+        g: g.clamp(0.0, 1.0),
+        b: b.clamp(0.0, 1.0),
+        a: a.clamp(0.0, 1.0),
+    }
+}
+
+// colors of the bounding boxes, cycled through in the order of the objects
+const BOUNDS_COLORS: [[f32; 4]; 6] = [
+    [1.0, 0.2, 0.2, 1.0],
+    [0.2, 1.0, 0.2, 1.0],
+    [0.2, 0.4, 1.0, 1.0],
+    [1.0, 1.0, 0.2, 1.0],
+    [1.0, 0.2, 1.0, 1.0],
+    [0.2, 1.0, 1.0, 1.0],
+];
+
+// formats tried when the surface has no preferred one, sRGB first
+const FALLBACK_SURFACE_FORMATS: [wgpu::TextureFormat; 4] = [
+    wgpu::TextureFormat::Bgra8UnormSrgb,
+    wgpu::TextureFormat::Rgba8UnormSrgb,
+    wgpu::TextureFormat::Bgra8Unorm,
+    wgpu::TextureFormat::Rgba8Unorm,
+];
+
+// some virtual adapters have no preferred format, wgpu 0.11 can't list the supported ones
+// so the first fallback the adapter can render to is picked
+fn surface_format(surface: &wgpu::Surface, adapter: &wgpu::Adapter) -> Option<wgpu::TextureFormat> {
+    surface.get_preferred_format(adapter).or_else(|| {
+        FALLBACK_SURFACE_FORMATS.into_iter().find(|format| {
+            adapter
+                .get_texture_format_features(*format)
+                .allowed_usages
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+        })
+    })
+}
+
+// samples per pixel every adapter supports for the surface & depth formats
+const SUPPORTED_SAMPLE_COUNTS: [u32; 2] = [1, 4];
+
+// `requested` when the color & depth formats can be multisampled with it, 1 otherwise
+fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    formats: [wgpu::TextureFormat; 2],
+    requested: u32,
+) -> u32 {
+    let renderable = formats.iter().all(|format| {
+        adapter
+            .get_texture_format_features(*format)
+            .allowed_usages
+            .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+    });
+    if renderable && SUPPORTED_SAMPLE_COUNTS.contains(&requested) {
+        requested
+    } else {
+        log::warn!(
+            "{} samples per pixel are not supported, MSAA is disabled",
+            requested
+        );
+        1
+    }
 }
 
 impl<'a> Scene {
     // Creating some of the wgpu types requires async code
-    async fn new(window: &Window, lens_objects: &mut Vec<LensObject<'a>>) -> Scene {
-        let size = window.inner_size();
-
+    async fn new(
+        window: Option<&Window>,
+        size: winit::dpi::PhysicalSize<u32>,
+        lens_scenes: &mut Vec<(String, Vec<LensObject<'a>>)>,
+        active_scene: &str,
+        scene_config: &SceneConfig,
+    ) -> anyhow::Result<Scene> {
         // The instance is a handle to our GPU
         // Backends::all => Vulkan + Metal + DX12 + Browser WebGPU
         let instance = wgpu::Instance::new(wgpu::Backends::all());
-        let surface = unsafe { instance.create_surface(window) };
+        let surface = window.map(|window| unsafe { instance.create_surface(window) });
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
+                compatible_surface: surface.as_ref(),
                 force_fallback_adapter: false,
             })
             .await
-            .unwrap();
+            .ok_or_else(|| anyhow::anyhow!("no adapter is compatible with the surface"))?;
+
+        // request the features of the polygon modes, objects fall back to Fill without them
+        let mut features = wgpu::Features::empty();
+        for object in lens_scenes
+            .iter_mut()
+            .flat_map(|(_, lens_objects)| lens_objects.iter_mut())
+        {
+            let options = &mut object.render_options;
+            if options.polygon_mode == wgpu::PolygonMode::Fill {
+                options.polygon_mode = scene_config.polygon_mode;
+            }
+            let feature = RenderOptions::polygon_mode_feature(options.polygon_mode);
+            if adapter.features().contains(feature) {
+                features |= feature;
+            } else {
+                log::warn!(
+                    "{:?} polygon mode is not supported by the adapter, the object is filled",
+                    options.polygon_mode
+                );
+                options.polygon_mode = wgpu::PolygonMode::Fill;
+            }
+        }
 
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::empty(),
+                    features,
                     limits: wgpu::Limits::default(),
                     label: None,
                 },
                 None, // Trace path
             )
-            .await
-            .unwrap();
+            .await?;
 
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_preferred_format(&adapter).unwrap(),
+            format: match surface.as_ref() {
+                Some(surface) => surface_format(surface, &adapter).ok_or_else(|| {
+                    anyhow::anyhow!("no format of the surface is supported by the adapter")
+                })?,
+                None => scene_config.headless_format,
+            },
             width: size.width,
             height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode: scene_config.present_mode,
         };
-        surface.configure(&device, &config);
+        if let Some(surface) = surface.as_ref() {
+            surface.configure(&device, &config);
+        }
 
-        let depth_texture =
-            texture::Texture::create_depth_texture(&device, &config, "depth_texture");
+        // the stencil buffer is only created when an object needs it
+        let depth_format = if lens_scenes
+            .iter()
+            .flat_map(|(_, lens_objects)| lens_objects)
+            .any(|object| object.render_options.stencil.is_some())
+        {
+            texture::Texture::DEPTH_STENCIL_FORMAT
+        } else {
+            texture::Texture::DEPTH_FORMAT
+        };
+        let sample_count = supported_sample_count(
+            &adapter,
+            [config.format, depth_format],
+            scene_config.sample_count,
+        );
+        let depth_texture = texture::Texture::create_depth_texture(
+            &device,
+            &config,
+            depth_format,
+            sample_count,
+            "depth_texture",
+        );
+        // the passes draw in it then resolve it in the scene color or on the surface
+        let msaa_color = (sample_count > 1).then(|| {
+            texture::Texture::create_multisampled_target(
+                &device,
+                &config,
+                sample_count,
+                "msaa_color",
+            )
+        });
+        let target_formats = renderer::TargetFormats {
+            color: config.format,
+            depth: depth_format,
+            sample_count,
+        };
+        let skybox = match scene_config.skybox.as_ref() {
+            Some(paths) => Some(skybox::Skybox::new(
+                &device,
+                texture::Texture::load_cubemap(&device, &queue, paths, "skybox")?,
+                &target_formats,
+            )),
+            None => None,
+        };
 
         // create the camera
-        let camera_binder = camera::Camera::new(&device, &config);
+        let mut camera_binder = camera::Camera::new(&device, &config);
+        camera_binder.set_fov(scene_config.fov);
+        let (speed, sensitivity, zoom_speed) = scene_config.camera_controls;
+        camera_binder.camera_controller.set_speed(speed);
+        camera_binder.camera_controller.set_sensitivity(sensitivity);
+        camera_binder.camera_controller.set_zoom_speed(zoom_speed);
+        if let Some((eye, target)) = scene_config.camera_origin {
+            camera_binder.set_origin(eye, target);
+        }
+        camera_binder.set_random_seed(scene_config.random_seed);
 
         // create light bind_group_layout and bind group
-        let light_uniform = light::LightUniform {
-            position: [2.0, 2.0, 2.0],
-            _padding: 0,
-            color: [0.2, 0.5, 0.7],
+        let shadow_map = shadow::ShadowMap::new(
+            &device,
+            &queue,
+            scene_config.shadow_map_size,
+            scene_config.shadow_bias,
+        );
+        let mut light_binder = match scene_config.lights.split_first() {
+            Some((first, others)) => {
+                let mut light_binder = light::Light::bind(
+                    &device,
+                    *first,
+                    &shadow_map,
+                    scene_config.max_lights_per_cluster,
+                );
+                for light_uniform in others {
+                    light_binder.add(*light_uniform);
+                }
+                // added lights stay in place
+                light_binder.set_rotation_speed(0.0);
+                light_binder
+            }
+            None => light::Light::bind(
+                &device,
+                light::LightUniform::new(
+                    light::LightKind::Point {
+                        position: [2.0, 2.0, 2.0],
+                    },
+                    [0.2, 0.5, 0.7],
+                    0.0,
+                ),
+                &shadow_map,
+                scene_config.max_lights_per_cluster,
+            ),
         };
-        let light_binder = light::Light::bind(&device, light_uniform);
-
-        let mut model_renderers = Vec::new();
-        for _ in 0..lens_objects.len() {
-            let object = lens_objects.pop().unwrap();
-            let (instances_data, instances_len) =
-                if let Some((data, len)) = object.instances.clone() {
-                    (Some(data.clone()), Some(len.clone()))
-                } else {
-                    (None, None)
-                };
-            let cube_renderer = ModelRenderer::new_renderer(
-                renderer::Model::load(&device, &queue, object.object).unwrap(),
+        light_binder.set_ambient(&queue, scene_config.ambient);
+
+        let scene_color = scene_color::SceneColor::new(&device, &config);
+        // the depth can't be sampled as a whole when it has a stencil aspect or several samples,
+        // the passes reading it are disabled with MSAA
+        let single_depth = depth_format == texture::Texture::DEPTH_FORMAT && sample_count == 1;
+        let taa = (scene_config.taa && single_depth)
+            .then(|| taa::Taa::new(&device, &config, &scene_color.texture, &depth_texture.view));
+        let aux_buffers = (scene_config.aux_buffers && sample_count == 1).then(|| {
+            aux_buffers::AuxBuffers::new(
                 &device,
                 &config,
-                &camera_binder,
-                &light_binder,
-                std::borrow::Cow::Borrowed(object.shader_file),
-                instances_data,
-                instances_len,
-            );
-            model_renderers.push(cube_renderer);
+                depth_format,
+                &camera_binder.bind_group_layout,
+            )
+        });
+        let depth_debug = single_depth
+            .then(|| depth_debug::DepthDebug::new(&device, &config, &depth_texture.view));
+
+        // renderers are kept in the order the objects were added
+        let mut scene_manager = SceneManager::new();
+        // anisotropic samplers fall back to isotropic filtering without the downlevel flag
+        let max_anisotropy = if adapter
+            .get_downlevel_properties()
+            .flags
+            .contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING)
+        {
+            texture::MAX_ANISOTROPY
+        } else {
+            log::info!("anisotropic filtering is not supported by the adapter");
+            1
+        };
+        let mut sampler_pool = texture::SamplerPool::with_max_anisotropy(max_anisotropy);
+        for (name, mut lens_objects) in lens_scenes.drain(..) {
+            let mut model_renderers = Vec::new();
+            for object in lens_objects.drain(..) {
+                let mut common_layouts = vec![&camera_binder.bind_group_layout];
+                if !object.render_options.unlit {
+                    common_layouts.push(&light_binder.bind_group_layout);
+                }
+                if object.render_options.samples_scene_color {
+                    common_layouts.push(&scene_color.bind_group_layout);
+                }
+                let texture_binder = renderer::TextureBinder::bind(
+                    &device,
+                    &queue,
+                    &object.textures,
+                    &mut sampler_pool,
+                )?;
+                // the transform places each instance of the object, or its single one
+                let instances = match (object.transform, object.instances) {
+                    (Some(transform), Some((instances, length))) => Some((
+                        instances
+                            .into_iter()
+                            .map(|instance| {
+                                InstanceRaw::new(transform * cgmath::Matrix4::from(instance.model))
+                                    .with_color(instance.color)
+                            })
+                            .collect(),
+                        length,
+                    )),
+                    (_, instances) => instances,
+                };
+                let mut cube_renderer = ModelRenderer::new_renderer(
+                    renderer::Model::load(&device, &queue, object.object, &mut sampler_pool)?,
+                    &device,
+                    &target_formats,
+                    &common_layouts,
+                    std::borrow::Cow::Borrowed(object.shader_file),
+                    instances,
+                    object.custom_instances,
+                    texture_binder,
+                    object.render_options,
+                );
+                if let Some(transform) = object.transform.filter(|_| !cube_renderer.is_instanced())
+                {
+                    cube_renderer.set_transform(&queue, transform);
+                }
+                for (distance, lod) in object.lods {
+                    let lod = renderer::Model::load(&device, &queue, lod, &mut sampler_pool)?;
+                    cube_renderer.add_lod(&device, distance, lod)?;
+                }
+                model_renderers.push(cube_renderer);
+            }
+            scene_manager.add_scene(&name, model_renderers);
         }
+        scene_manager.activate_scene(active_scene);
+
+        let line_renderer = lines::LineRenderer::new(
+            &device,
+            &config,
+            depth_format,
+            sample_count,
+            &camera_binder,
+            scene_config.line_width,
+        );
 
-        Self {
+        let overlay_renderer =
+            overlay::OverlayRenderer::new(&device, &config, depth_format, sample_count);
+        let stats_overlay = overlay::Overlay::new(
+            &device,
+            &queue,
+            &overlay_renderer,
+            &image::DynamicImage::ImageRgba8(text::render_text(&[], STATS_SIZE.0, STATS_SIZE.1, 2)),
+            [8.0, 8.0, STATS_SIZE.0 as f32, STATS_SIZE.1 as f32],
+            (config.width, config.height),
+        );
+        let overlays = scene_config
+            .overlays
+            .iter()
+            .map(|(img, rect)| {
+                overlay::Overlay::new(
+                    &device,
+                    &queue,
+                    &overlay_renderer,
+                    img,
+                    *rect,
+                    (config.width, config.height),
+                )
+            })
+            .collect();
+
+        let minimap = scene_config.minimap.map(|(rect, height)| {
+            minimap::Minimap::new(&device, &queue, &config, &overlay_renderer, rect, height)
+        });
+
+        let mut scene = Self {
             surface,
             device,
             queue,
             config,
             size,
+            depth_format,
             depth_texture,
+            sample_count,
+            msaa_color,
             mouse_pressed: false,
-            camera_binder,
+            cursor_position: None,
+            cursor_grabbed: false,
+            cameras: vec![camera_binder],
+            active_camera: 0,
             light_binder,
-            model_renderers,
+            shadow_map,
+            scene_manager,
+            sampler_pool,
+            scene_color,
+            taa,
+            aux_buffers,
+            depth_debug,
+            show_depth: false,
+            line_renderer,
+            overlay_renderer,
+            render_stats: RenderStats::default(),
+            stats_overlay,
+            overlays,
+            minimap,
+            show_stats: false,
+            show_bounds: scene_config.show_bounds,
+            clear_color: scene_config.clear_color,
+            skybox,
+            stats_timer: std::time::Duration::ZERO,
+            stats_frames: 0,
+            frame_index: 0,
+            elapsed: std::time::Duration::ZERO,
+        };
+        if let Some(id) = scene_config.look_at_object {
+            scene.look_at_object(id);
         }
+
+        Ok(scene)
     }
 
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
-            self.camera_binder
-                .projection
-                .resize(new_size.width, new_size.height);
+            for camera in self.cameras.iter_mut() {
+                camera.projection.resize(new_size.width, new_size.height);
+            }
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
-            self.depth_texture =
-                texture::Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
+            self.configure_surface();
+            self.depth_texture = texture::Texture::create_depth_texture(
+                &self.device,
+                &self.config,
+                self.depth_format,
+                self.sample_count,
+                "depth_texture",
+            );
+            if self.msaa_color.is_some() {
+                self.msaa_color = Some(texture::Texture::create_multisampled_target(
+                    &self.device,
+                    &self.config,
+                    self.sample_count,
+                    "msaa_color",
+                ));
+            }
+            self.scene_color.resize(&self.device, &self.config);
+            if let Some(taa) = self.taa.as_mut() {
+                taa.resize(
+                    &self.device,
+                    &self.config,
+                    &self.scene_color.texture,
+                    &self.depth_texture.view,
+                );
+            }
+            if let Some(aux_buffers) = self.aux_buffers.as_mut() {
+                aux_buffers.resize(&self.device, &self.config);
+            }
+            if let Some(depth_debug) = self.depth_debug.as_mut() {
+                depth_debug.resize(&self.device, &self.depth_texture.view);
+            }
+            self.line_renderer
+                .resize(&self.queue, new_size.width, new_size.height);
+            self.stats_overlay
+                .resize(&self.queue, new_size.width, new_size.height);
+            for overlay in self.overlays.iter_mut() {
+                overlay.resize(&self.queue, new_size.width, new_size.height);
+            }
+            if let Some(minimap) = self.minimap.as_mut() {
+                minimap.resize(&self.queue, new_size.width, new_size.height);
+            }
         }
     }
 
@@ -140,24 +673,26 @@ impl<'a> Scene {
                 virtual_keycode: Some(key),
                 state,
                 ..
-            }) => self
-                .camera_binder
+            }) => self.cameras[self.active_camera]
                 .camera_controller
                 .process_keyboard(*key, *state),
             DeviceEvent::MouseWheel { delta, .. } => {
-                self.camera_binder.camera_controller.process_scroll(delta);
+                self.cameras[self.active_camera]
+                    .camera_controller
+                    .process_scroll(delta);
                 true
             }
-            DeviceEvent::Button {
-                button: 1, // Left Mouse Button
-                state,
-            } => {
+            DeviceEvent::Button { button, state }
+                if self.cameras[self.active_camera]
+                    .camera_controller
+                    .is_look_button(*button) =>
+            {
                 self.mouse_pressed = *state == ElementState::Pressed;
                 true
             }
             DeviceEvent::MouseMotion { delta } => {
-                if self.mouse_pressed {
-                    self.camera_binder
+                if self.mouse_pressed || self.cursor_grabbed {
+                    self.cameras[self.active_camera]
                         .camera_controller
                         .process_mouse(delta.0, delta.1);
                 }
@@ -167,106 +702,1424 @@ impl<'a> Scene {
         }
     }
 
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    // copy `range` of a buffer created with COPY_SRC back to the CPU, waiting for the GPU.
+    // The range must be aligned to 4 bytes and hold whole values of T.
+    pub fn read_buffer<T: bytemuck::Pod>(
+        &self,
+        buffer: &wgpu::Buffer,
+        range: std::ops::Range<wgpu::BufferAddress>,
+    ) -> anyhow::Result<Vec<T>> {
+        if range.end < range.start {
+            anyhow::bail!("buffer range {:?} ends before its start", range);
+        }
+        let size = range.end - range.start;
+        if size == 0 {
+            return Ok(Vec::new());
+        }
+        if range.start % wgpu::COPY_BUFFER_ALIGNMENT != 0 || size % wgpu::COPY_BUFFER_ALIGNMENT != 0
+        {
+            anyhow::bail!("buffer range {:?} is not aligned to 4 bytes", range);
+        }
+        if size % std::mem::size_of::<T>().max(1) as u64 != 0 {
+            anyhow::bail!(
+                "buffer range {:?} doesn't hold whole values of {} bytes",
+                range,
+                std::mem::size_of::<T>()
+            );
+        }
+
+        let staging_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Read Staging Buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Read Buffer Encoder"),
+            });
+        encoder.copy_buffer_to_buffer(buffer, range.start, &staging_buffer, 0, size);
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = staging_buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        self.device.poll(wgpu::Maintain::Wait);
+        pollster::block_on(mapping)?;
+        let values = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        staging_buffer.unmap();
+        Ok(values)
+    }
+
+    // samples per pixel of the scene, 1 when MSAA is disabled or unsupported
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    // format of the surface, needed by custom pipelines rendering on it
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.config.format
+    }
+
+    // depth of the last rendered frame, recreated when the window is resized.
+    // With a stencil buffer, sample it through a view of the depth aspect only,
+    // with MSAA it is multisampled.
+    pub fn depth_view(&self) -> &wgpu::TextureView {
+        &self.depth_texture.view
+    }
+
+    // color of the opaque objects, sampled by objects drawn after them
+    pub fn scene_color_view(&self) -> &wgpu::TextureView {
+        &self.scene_color.texture.view
+    }
+
+    // world normal of the opaque objects, when the aux buffers are enabled
+    pub fn aux_normal_view(&self) -> Option<&wgpu::TextureView> {
+        self.aux_buffers
+            .as_ref()
+            .map(|aux_buffers| &aux_buffers.normal.view)
+    }
+
+    // world position of the opaque objects, when the aux buffers are enabled
+    pub fn aux_position_view(&self) -> Option<&wgpu::TextureView> {
+        self.aux_buffers
+            .as_ref()
+            .map(|aux_buffers| &aux_buffers.position.view)
+    }
+
+    // the light can't be borrowed with the queue, these write it right away
+    pub fn set_light_position(&mut self, position: [f32; 3]) {
+        self.light_binder.set_position(&self.queue, position);
+    }
+
+    pub fn set_light_color(&mut self, color: [f32; 3]) {
+        self.light_binder.set_color(&self.queue, color);
+    }
+
+    pub fn set_ambient(&mut self, ambient: [f32; 3]) {
+        self.light_binder.set_ambient(&self.queue, ambient);
+    }
+
+    // used from the next frame, channels are clamped to [0, 1]
+    pub fn set_clear_color(&mut self, r: f64, g: f64, b: f64, a: f64) {
+        self.clear_color = clear_color(r, g, b, a);
+    }
+
+    pub fn clear_color(&self) -> wgpu::Color {
+        self.clear_color
+    }
+
+    fn camera(&self) -> &camera::Camera {
+        &self.cameras[self.active_camera]
+    }
+
+    // the active camera
+    pub fn camera_mut(&mut self) -> &mut camera::Camera {
+        &mut self.cameras[self.active_camera]
+    }
+
+    // add a camera to switch to, e.g. from `create_camera`, returns its index. The first
+    // camera of the scene has index 0
+    pub fn add_camera(&mut self, camera: camera::Camera) -> usize {
+        self.cameras.push(camera);
+        self.cameras.len() - 1
+    }
+
+    // render from another camera & send it the input, false when there is no camera at
+    // `index`. Each camera keeps its own view & controller state
+    pub fn set_active_camera(&mut self, index: usize) -> bool {
+        if index >= self.cameras.len() {
+            return false;
+        }
+        if index != self.active_camera {
+            self.active_camera = index;
+            // the history was seen from the previous camera
+            if let Some(taa) = self.taa.as_mut() {
+                taa.reset_history();
+            }
+            self.mouse_pressed = false;
+        }
+        true
+    }
+
+    pub fn active_camera(&self) -> usize {
+        self.active_camera
+    }
+
+    pub fn cameras_mut(&mut self) -> &mut [camera::Camera] {
+        &mut self.cameras
+    }
+
+    pub fn light_mut(&mut self) -> &mut light::Light {
+        &mut self.light_binder
+    }
+
+    // renderer of an object of any scene, None once removed
+    pub fn object(&self, id: ObjectId) -> Option<&ModelRenderer> {
+        self.scene_manager.get(id)
+    }
+
+    // change an object between frames, e.g. its visibility, vertices or instances. Its render
+    // options are read only, the pipelines were built from them
+    pub fn object_mut(&mut self, id: ObjectId) -> Option<&mut ModelRenderer> {
+        self.scene_manager.get_mut(id)
+    }
+
+    // release an object of any scene, the ids of the other objects stay valid.
+    // Returns false when it was already removed
+    pub fn remove_object(&mut self, id: ObjectId) -> bool {
+        self.scene_manager.remove(id).is_some()
+    }
+
+    pub fn set_object_visible(&mut self, id: ObjectId, visible: bool) {
+        if let Some(renderer) = self.scene_manager.get_mut(id) {
+            renderer.visible = visible;
+        }
+    }
+
+    // smooth normals of an object from the current positions of its vertices, e.g. after
+    // moving them through `object_mut`. The whole vertex buffer is uploaded with them
+    pub fn recompute_normals(&mut self, id: ObjectId) {
+        if let Some(renderer) = self.scene_manager.get_mut(id) {
+            renderer.model.recompute_normals(&self.device, &self.queue);
+        }
+    }
+
+    pub fn set_morph_weights(&mut self, id: ObjectId, weights: &[f32]) {
+        if let Some(renderer) = self.scene_manager.get_mut(id) {
+            renderer.set_morph_weights(&self.queue, weights);
+        }
+    }
+
+    // replace the instances of an object added with instances, see
+    // `ModelRenderer::update_instances`
+    pub fn update_instances(&mut self, id: ObjectId, instances: &[InstanceRaw]) {
+        if let Some(renderer) = self.scene_manager.get_mut(id) {
+            renderer.update_instances(&self.device, &self.queue, instances);
+        }
+    }
+
+    // move an object added without instances, see `ModelRenderer::set_transform`
+    pub fn set_object_transform(&mut self, id: ObjectId, transform: cgmath::Matrix4<f32>) {
+        if let Some(renderer) = self.scene_manager.get_mut(id) {
+            renderer.set_transform(&self.queue, transform);
+        }
+    }
+
+    // draw a line for the next frame only
+    pub fn draw_line(&mut self, start: [f32; 3], end: [f32; 3], color: [f32; 4]) {
+        self.line_renderer.push(start, end, color);
+    }
+
+    pub fn set_line_width(&mut self, line_width: f32) {
+        self.line_renderer.set_width(&self.queue, line_width);
+    }
+
+    // frame the camera on the bounds of an object
+    pub fn look_at_object(&mut self, id: ObjectId) {
+        if let Some(bounds) = self.scene_manager.get(id).and_then(|renderer| {
+            Aabb::from_positions(
+                renderer
+                    .world_bounds()
+                    .iter()
+                    .flat_map(|bounds| bounds.corners()),
+            )
+        }) {
+            self.cameras[self.active_camera].frame(bounds.center(), bounds.radius());
+            self.cameras[self.active_camera].update(&self.queue, std::time::Duration::ZERO);
+        }
+    }
+
+    // back to the view given to `Lens::with_camera_origin`, or the default one
+    pub fn reset_camera(&mut self) {
+        self.cameras[self.active_camera].reset();
+    }
+
+    // a camera for `draw_into` or `add_camera`, independent from the scene camera
+    pub fn create_camera(&self) -> camera::Camera {
+        let mut camera = camera::Camera::new(&self.device, &self.config);
+        camera.set_fov(self.camera().fov());
+        camera
+    }
+
+    // record the opaque objects seen from `camera` into `target`, e.g. for reflections.
+    // The target must have the surface format and size, the scene depth buffer is reused.
+    pub fn draw_into(
+        &self,
+        target: &wgpu::TextureView,
+        camera: &camera::Camera,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        let bind_groups = &[&camera.bind_group, &self.light_binder.bind_group];
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Draw Into Pass"),
+            color_attachments: &[
+                self.color_attachment(target, wgpu::LoadOp::Clear(self.clear_color))
+            ],
+            depth_stencil_attachment: Some(self.depth_stencil_attachment(true)),
+        });
+
+        self.draw_opaque(
+            &mut render_pass,
+            bind_groups,
+            self.full_viewport(),
+            &Frustum::from_view_proj(camera.view_proj()),
+            &mut RenderStats::default(),
+        );
+    }
+
+    // switch the drawn objects, returns false if no scene has this name
+    pub fn activate_scene(&mut self, name: &str) -> bool {
+        self.scene_manager.activate_scene(name)
+    }
+
+    pub fn active_scene(&self) -> &str {
+        self.scene_manager.active_scene()
+    }
+
+    pub fn resource_stats(&self) -> ResourceStats {
+        ResourceStats {
+            materials: self
+                .scene_manager
+                .all_renderers()
+                .map(|renderer| renderer.model.materials.as_ref().map_or(0, |m| m.len()))
+                .sum(),
+            samplers: self.sampler_pool.len(),
+        }
+    }
+
+    // grab & hide the cursor for FPS-style looking, toggled with G and released with Escape
+    pub fn set_cursor_grab(&mut self, grab: bool) {
+        self.cursor_grabbed = grab;
+    }
+
+    pub fn cursor_grabbed(&self) -> bool {
+        self.cursor_grabbed
+    }
+
+    // in physical pixels from the top left corner of the window
+    pub fn cursor_position(&self) -> Option<winit::dpi::PhysicalPosition<f64>> {
+        self.cursor_position
+    }
+
+    // nearest visible object of the active scene under a pixel of the window, e.g. `cursor_position`
+    pub fn pick(&self, pos: winit::dpi::PhysicalPosition<f64>) -> Option<Pick> {
+        let (origin, direction) = self.camera().screen_ray(pos, self.size);
+        self.scene_manager
+            .objects()
+            .filter(|(_, renderer)| renderer.visible)
+            .filter_map(|(id, renderer)| {
+                picking::intersect(renderer, origin, direction).map(|hit| Pick {
+                    object: id,
+                    mesh: hit.mesh,
+                    instance: hit.instance,
+                    triangle: hit.triangle,
+                    t: hit.t,
+                    barycentric: hit.barycentric,
+                    position: origin + direction * hit.t,
+                })
+            })
+            .min_by(|a, b| a.t.total_cmp(&b.t))
+    }
+
+    // present with Fifo when enabled, Immediate otherwise, see `set_present_mode`
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.set_present_mode(if vsync {
+            wgpu::PresentMode::Fifo
+        } else {
+            wgpu::PresentMode::Immediate
+        });
+    }
+
+    // depth bias of the objects drawn in the shadow map, see `Lens::set_shadow_bias`
+    pub fn set_shadow_bias(&mut self, constant: i32, slope: f32) {
+        self.shadow_map.set_bias(&self.device, constant, slope);
+    }
+
+    pub fn shadow_bias(&self) -> (i32, f32) {
+        self.shadow_map.bias()
+    }
+
+    // reconfigure the surface with `present_mode`, e.g. Mailbox or Immediate to measure the
+    // frame rate without vsync. wgpu 0.11 can't list the supported modes, it falls back to
+    // Fifo with a warning when the surface doesn't support the mode
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        self.config.present_mode = present_mode;
+        self.configure_surface();
+    }
+
+    // mode requested for the surface, the one presenting may be Fifo, see `set_present_mode`
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.config.present_mode
+    }
+
+    fn configure_surface(&self) {
+        if let Some(surface) = self.surface.as_ref() {
+            surface.configure(&self.device, &self.config);
+        }
+    }
+
+    pub fn vsync(&self) -> bool {
+        self.config.present_mode == wgpu::PresentMode::Fifo
+    }
+
+    // write the objects of the active scene with their instances in a binary glTF file,
+    // see `gltf_export` for what is left out
+    pub fn export_gltf<P: AsRef<std::path::Path>>(&self, path: P) -> anyhow::Result<()> {
+        gltf_export::write_glb(self.scene_manager.visible_renderers(), path)
+    }
+
+    // number of frames rendered so far
+    pub fn frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    // total time since the scene started
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.elapsed
+    }
+
+    pub fn render_stats(&self) -> RenderStats {
+        self.render_stats
+    }
+
+    pub fn toggle_stats(&mut self) {
+        self.show_stats = !self.show_stats;
+    }
+
+    // not available with a stencil buffer, the depth can't be sampled alone
+    pub fn toggle_depth_view(&mut self) {
+        self.show_depth = !self.show_depth && self.depth_debug.is_some();
+    }
+
+    pub fn set_show_bounds(&mut self, show_bounds: bool) {
+        self.show_bounds = show_bounds;
+    }
+
+    pub fn toggle_bounds(&mut self) {
+        self.show_bounds = !self.show_bounds;
+    }
+
+    // queue the boxes of the active scene, read again each frame to follow the objects
+    fn push_bounds(&mut self) {
+        for (id, renderer) in self.scene_manager.objects() {
+            if !renderer.visible {
+                continue;
+            }
+            let color = BOUNDS_COLORS[id.index() % BOUNDS_COLORS.len()];
+            for bounds in renderer.world_bounds() {
+                self.line_renderer.push_aabb(&bounds, color);
+            }
+        }
+    }
+
+    fn update_stats_overlay(&mut self, dt: std::time::Duration) {
+        self.stats_timer += dt;
+        self.stats_frames += 1;
+        // refresh a few times per second to keep the numbers readable
+        if !self.show_stats || self.stats_timer.as_secs_f32() < 0.25 {
+            return;
+        }
+
+        let fps = self.stats_frames as f32 / self.stats_timer.as_secs_f32();
+        self.stats_timer = std::time::Duration::ZERO;
+        self.stats_frames = 0;
+
+        let lines = [
+            format!("FPS: {:.0}", fps),
+            format!("DRAW CALLS: {}", self.render_stats.draw_calls),
+            format!("TRIANGLES: {}", self.render_stats.triangles),
+            format!("INSTANCES: {}", self.render_stats.instances),
+            format!("CULLED: {}", self.render_stats.culled),
+        ];
+        let img = text::render_text(&lines, STATS_SIZE.0, STATS_SIZE.1, 2);
+        self.stats_overlay
+            .update_image(&self.queue, &image::DynamicImage::ImageRgba8(img));
+    }
+
     fn update(&mut self, dt: std::time::Duration) {
+        self.elapsed += dt;
+        self.update_stats_overlay(dt);
+
         // update camera position
-        self.camera_binder.update(&self.queue, dt);
+        if let Some(taa) = self.taa.as_mut() {
+            self.cameras[self.active_camera].set_jitter(taa.next_jitter());
+        }
+        self.cameras[self.active_camera].set_time(self.elapsed.as_secs_f32());
+        self.cameras[self.active_camera].update(&self.queue, dt);
+        if let Some(skybox) = self.skybox.as_ref() {
+            skybox.update(&self.queue, self.camera().view_proj());
+        }
+        if let Some(minimap) = self.minimap.as_mut() {
+            minimap.camera.set_time(self.elapsed.as_secs_f32());
+            minimap.follow(&self.queue, self.cameras[self.active_camera].position());
+        }
 
         // Update the light
         self.light_binder.update(&self.queue, dt);
+        if self.shadow_map.is_enabled() {
+            let bounds = Aabb::from_positions(
+                self.scene_manager
+                    .visible_renderers()
+                    .filter(|renderer| renderer.casts_shadows())
+                    .flat_map(|renderer| renderer.world_bounds())
+                    .flat_map(|bounds| [bounds.min, bounds.max]),
+            );
+            self.shadow_map
+                .update(&self.queue, &self.light_binder.lights()[0], bounds);
+        }
+
+        let eye = self.camera().position();
+        for renderer in self.scene_manager.renderers_mut() {
+            renderer.select_lod(eye);
+            let lod_models = renderer.lods.iter_mut().map(|(_, model)| model);
+            for model in std::iter::once(&mut renderer.model).chain(lod_models) {
+                for material in model.materials.iter_mut().flatten() {
+                    material.update_atlas(&self.queue, self.elapsed);
+                }
+            }
+        }
+
+        if self.show_bounds {
+            self.push_bounds();
+        }
+    }
+
+    // x, y, width, height of the whole window
+    fn full_viewport(&self) -> [f32; 4] {
+        [
+            0.0,
+            0.0,
+            self.config.width as f32,
+            self.config.height as f32,
+        ]
+    }
+
+    // `viewport` is the one set on the render pass, restored after objects changing its depth
+    fn draw_renderer<'b>(
+        &self,
+        render_pass: &mut wgpu::RenderPass<'b>,
+        renderer: &'b ModelRenderer,
+        bind_groups: &'b [&'b wgpu::BindGroup],
+        viewport: [f32; 4],
+        frustum: &Frustum,
+        render_stats: &mut RenderStats,
+    ) {
+        if let Some(stencil) = renderer.render_options().stencil {
+            render_pass.set_stencil_reference(stencil.reference());
+        }
+        let [x, y, width, height] = viewport;
+        if let Some((min_depth, max_depth)) = renderer.render_options().viewport_depth {
+            render_pass.set_viewport(x, y, width, height, min_depth, max_depth);
+            render_pass.draw_model(renderer, bind_groups, Some(frustum), render_stats);
+            render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        } else {
+            render_pass.draw_model(renderer, bind_groups, Some(frustum), render_stats);
+        }
+    }
+
+    // attachment drawing on `target`, through the multisampled color resolved into it with MSAA
+    fn color_attachment<'b>(
+        &'b self,
+        target: &'b wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'b> {
+        let ops = wgpu::Operations { load, store: true };
+        match self.msaa_color.as_ref() {
+            Some(msaa_color) => wgpu::RenderPassColorAttachment {
+                view: &msaa_color.view,
+                resolve_target: Some(target),
+                ops,
+            },
+            None => wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops,
+            },
+        }
+    }
+
+    // depth & stencil are cleared by the first pass of a frame and loaded by the next ones
+    fn depth_stencil_attachment(&self, clear: bool) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: &self.depth_texture.view,
+            depth_ops: Some(wgpu::Operations {
+                load: if clear {
+                    wgpu::LoadOp::Clear(1.0)
+                } else {
+                    wgpu::LoadOp::Load
+                },
+                store: true,
+            }),
+            stencil_ops: (self.depth_format == texture::Texture::DEPTH_STENCIL_FORMAT).then_some(
+                wgpu::Operations {
+                    load: if clear {
+                        wgpu::LoadOp::Clear(0)
+                    } else {
+                        wgpu::LoadOp::Load
+                    },
+                    store: true,
+                },
+            ),
+        }
+    }
+
+    // the objects that don't sample the scene color, the transparent ones included
+    fn draw_opaque<'b>(
+        &'b self,
+        render_pass: &mut wgpu::RenderPass<'b>,
+        bind_groups: &'b [&'b wgpu::BindGroup],
+        viewport: [f32; 4],
+        frustum: &Frustum,
+        render_stats: &mut RenderStats,
+    ) {
+        // the background group first and the transparent one last,
+        // in the order the objects were added
+        let group = |options: &RenderOptions| match (options.background, options.transparent) {
+            (true, _) => 0,
+            (false, false) => 1,
+            (false, true) => 2,
+        };
+        for drawn_group in 0..3 {
+            for renderer in self.scene_manager.visible_renderers() {
+                let options = renderer.render_options();
+                if !options.samples_scene_color && group(options) == drawn_group {
+                    self.draw_renderer(
+                        render_pass,
+                        renderer,
+                        bind_groups,
+                        viewport,
+                        frustum,
+                        render_stats,
+                    );
+                }
+            }
+        }
     }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
+        // headless scenes only draw in `capture_frame`
+        let output = match self.surface.as_ref() {
+            Some(surface) => surface.get_current_texture()?,
+            None => return Ok(()),
+        };
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
+        let encoder = self.record_frame(&view);
+
+        // submit will accept anything that implements IntoIter
+        self.queue.submit(std::iter::once(encoder.finish()));
+        output.present();
+
+        Ok(())
+    }
+
+    // render the next frame in an offscreen texture of the surface size & format and read
+    // it back, waiting for the GPU, e.g. for screenshots or visual tests
+    pub fn capture_frame(&mut self) -> anyhow::Result<image::RgbaImage> {
+        let bgra = match self.config.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => anyhow::bail!("frames of format {:?} can't be captured", format),
+        };
+        let size = wgpu::Extent3d {
+            width: self.config.width,
+            height: self.config.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Capture Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self.record_frame(&view);
+
+        // the rows of a texture copy are aligned to 256 bytes, the padding is dropped below
+        let row_size = 4 * size.width;
+        let padded_row_size = row_size.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let staging_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Staging Buffer"),
+            size: (padded_row_size * size.height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &staging_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_row_size),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = staging_buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        self.device.poll(wgpu::Maintain::Wait);
+        pollster::block_on(mapping)?;
+        let mut pixels = Vec::with_capacity((row_size * size.height) as usize);
+        for row in slice
+            .get_mapped_range()
+            .chunks_exact(padded_row_size as usize)
+        {
+            pixels.extend_from_slice(&row[..row_size as usize]);
+        }
+        staging_buffer.unmap();
+        if bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        image::RgbaImage::from_raw(size.width, size.height, pixels)
+            .ok_or_else(|| anyhow::anyhow!("the captured frame has the wrong size"))
+    }
+
+    // record all the passes of a frame drawing on `view`, the encoder is left to submit
+    fn record_frame(&mut self, view: &wgpu::TextureView) -> wgpu::CommandEncoder {
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("Render Encoder"),
             });
 
+        self.line_renderer.prepare(&self.device, &self.queue);
+        // the lights of each cluster are read by the lit objects of the following passes
+        self.light_binder.assign_clusters(
+            &self.queue,
+            &mut encoder,
+            &self.cameras[self.active_camera],
+        );
+
         // create bind_groups for each model to render
-        let bind_groups = &[
-            &self.camera_binder.bind_group,
-            &self.light_binder.bind_group,
-        ];
+        let bind_groups = &[&self.camera().bind_group, &self.light_binder.bind_group];
+        let mut render_stats = RenderStats::default();
+        // the meshes out of the view of the camera drawing them are skipped
+        let frustum = Frustum::from_view_proj(self.camera().view_proj());
+
+        // the shadow map is sampled by the lit objects of the following passes
+        self.shadow_map
+            .draw(&mut encoder, self.scene_manager.visible_renderers());
 
+        // opaque objects are rendered offscreen
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[
                     // This is what [[location(0)]] in the fragment shader targets
-                    wgpu::RenderPassColorAttachment {
-                        view: &view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color {
-                                r: 0.1,
-                                g: 0.2,
-                                b: 0.3,
-                                a: 1.0,
-                            }),
-                            store: true,
-                        },
-                    },
+                    self.color_attachment(
+                        &self.scene_color.texture.view,
+                        wgpu::LoadOp::Clear(self.clear_color),
+                    ),
                 ],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: true,
-                    }),
-                    stencil_ops: None,
-                }),
+                depth_stencil_attachment: Some(self.depth_stencil_attachment(true)),
             });
 
-            for renderer in &self.model_renderers {
-                render_pass.draw_model(renderer, bind_groups);
+            if let Some(skybox) = self.skybox.as_ref() {
+                skybox.draw(&mut render_pass);
             }
+            self.draw_opaque(
+                &mut render_pass,
+                bind_groups,
+                self.full_viewport(),
+                &frustum,
+                &mut render_stats,
+            );
         }
-        // submit will accept anything that implements IntoIter
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
 
-        Ok(())
+        if let Some(aux_buffers) = self.aux_buffers.as_ref() {
+            aux_buffers.draw(
+                &mut encoder,
+                self.scene_manager.visible_renderers(),
+                &self.camera().bind_group,
+                self.depth_stencil_attachment(false),
+            );
+        }
+
+        if let Some(taa) = self.taa.as_mut() {
+            taa.resolve(
+                &self.queue,
+                &mut encoder,
+                &self.scene_color.texture,
+                self.cameras[self.active_camera].view_proj(),
+            );
+        }
+        match self.depth_debug.as_ref().filter(|_| self.show_depth) {
+            Some(depth_debug) => {
+                depth_debug.draw(&self.queue, &mut encoder, view, &self.camera().projection)
+            }
+            // with MSAA the multisampled color is resolved on the surface by the next pass
+            None if self.msaa_color.is_some() => (),
+            None => self.scene_color.blit(&mut encoder, view),
+        }
+
+        // objects sampling the scene color and helpers are drawn on top
+        let scene_color_bind_groups = &[
+            &self.camera().bind_group,
+            &self.light_binder.bind_group,
+            &self.scene_color.bind_group,
+        ];
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Scene Color Pass"),
+                color_attachments: &[self.color_attachment(view, wgpu::LoadOp::Load)],
+                depth_stencil_attachment: Some(self.depth_stencil_attachment(false)),
+            });
+
+            for renderer in self.scene_manager.visible_renderers() {
+                if renderer.render_options().samples_scene_color {
+                    self.draw_renderer(
+                        &mut render_pass,
+                        renderer,
+                        scene_color_bind_groups,
+                        self.full_viewport(),
+                        &frustum,
+                        &mut render_stats,
+                    );
+                }
+            }
+
+            render_pass.draw_lines(&self.line_renderer, &self.camera().bind_group);
+
+            for overlay in &self.overlays {
+                render_pass.draw_overlay(&self.overlay_renderer, overlay);
+            }
+            if self.show_stats {
+                render_pass.draw_overlay(&self.overlay_renderer, &self.stats_overlay);
+            }
+        }
+        // the depth of the scene is no longer needed, it is reused by the minimap
+        if let Some(minimap) = self.minimap.as_ref() {
+            let minimap_bind_groups = &[&minimap.camera.bind_group, &self.light_binder.bind_group];
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Minimap Pass"),
+                color_attachments: &[self.color_attachment(view, wgpu::LoadOp::Load)],
+                depth_stencil_attachment: Some(self.depth_stencil_attachment(true)),
+            });
+
+            render_pass.draw_overlay(&self.overlay_renderer, &minimap.background);
+            if let Some(viewport) = minimap.viewport(self.config.width, self.config.height) {
+                let [x, y, width, height] = viewport;
+                render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+                render_pass.set_scissor_rect(x as u32, y as u32, width as u32, height as u32);
+                self.draw_opaque(
+                    &mut render_pass,
+                    minimap_bind_groups,
+                    viewport,
+                    &Frustum::from_view_proj(minimap.camera.view_proj()),
+                    &mut render_stats,
+                );
+            }
+        }
+        self.render_stats = render_stats;
+        self.frame_index += 1;
+
+        encoder
     }
 }
 
 pub struct LensObject<'a> {
     pub object: Object,
-    pub position: cgmath::Vector3<f32>,
+    // model matrix of the object, applied to each of its instances when it has some
     pub transform: Option<cgmath::Matrix4<f32>>,
     pub shader_file: &'a str,
     pub instances: Option<(Vec<InstanceRaw>, usize)>,
+    pub custom_instances: Option<CustomInstances>,
+    // textures bound in their own group after the standard ones
+    pub textures: Vec<(image::DynamicImage, SamplerConfig)>,
+    pub render_options: RenderOptions,
+    // lower detail objects with the camera distance they are drawn from
+    pub lods: Vec<(f32, Object)>,
+}
+
+impl<'a> LensObject<'a> {
+    pub fn new(object: Object, shader_file: &'a str) -> LensObject<'a> {
+        LensObject {
+            object,
+            transform: None,
+            shader_file,
+            instances: None,
+            custom_instances: None,
+            textures: Vec::new(),
+            render_options: RenderOptions::default(),
+            lods: Vec::new(),
+        }
+    }
+
+    // place the object, its shader reads the model matrix at locations 5 to 8 like the
+    // instanced ones
+    pub fn with_transform(mut self, transform: cgmath::Matrix4<f32>) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    // bind the color of the opaque objects for refraction or distortion effects
+    pub fn sample_scene_color(mut self) -> Self {
+        self.render_options.samples_scene_color = true;
+        self
+    }
+
+    // draw the object in a narrow depth band, e.g. to keep gizmos in front
+    pub fn with_viewport_depth(mut self, min_depth: f32, max_depth: f32) -> Self {
+        self.render_options.viewport_depth = Some((min_depth, max_depth));
+        self
+    }
+
+    // blend the object over the surface it lies on without z-fighting
+    pub fn as_decal(mut self) -> Self {
+        self.render_options.blend = wgpu::BlendState::ALPHA_BLENDING;
+        self.render_options.depth_write_enabled = false;
+        self.render_options.depth_bias = RenderOptions::DECAL_DEPTH_BIAS;
+        self
+    }
+
+    // blend the object with its alpha over the opaque objects, e.g. glass or smoke.
+    // Transparent objects are drawn after the opaque ones, in the order they were added
+    pub fn as_transparent(mut self) -> Self {
+        self.render_options.transparent = true;
+        self.render_options.blend = wgpu::BlendState::ALPHA_BLENDING;
+        self.render_options.depth_write_enabled = false;
+        self
+    }
+
+    // test of the object depth against the scene one, `Less` by default. With `Greater`
+    // only the parts hidden by the objects drawn before are visible, e.g. a silhouette
+    // seen through walls, usually added as a second object without depth write
+    pub fn with_depth_compare(mut self, depth_compare: wgpu::CompareFunction) -> Self {
+        self.render_options.depth_compare = depth_compare;
+        self
+    }
+
+    // draw the object with animated water waves, blended like `as_transparent`.
+    // The instances and the shader of the object are ignored
+    pub fn as_water(mut self, params: WaterParams) -> Self {
+        self.render_options.water = Some(params);
+        self.as_transparent()
+    }
+
+    // draw the object behind all the others, e.g. a sun disc or distant mountains
+    pub fn as_background(mut self) -> Self {
+        self.render_options.background = true;
+        self.render_options.depth_write_enabled = false;
+        self
+    }
+
+    // the instance buffer will be updated in place with `queue.write_buffer`
+    pub fn with_dynamic_instances(mut self) -> Self {
+        self.render_options.dynamic_instances = true;
+        self
+    }
+
+    // draw each object in place of this one once the camera is farther than its distance
+    // from the bounds, e.g. decimated versions of the model. They are drawn with the shader
+    // of this object, so they must have materials when it has some
+    pub fn with_lods(mut self, lods: Vec<(f32, Object)>) -> Self {
+        self.lods.extend(lods);
+        self
+    }
+
+    // extra per-instance attributes read by the shader, e.g. a value for color mapping
+    pub fn with_custom_instances(mut self, custom_instances: CustomInstances) -> Self {
+        self.custom_instances = Some(custom_instances);
+        self
+    }
+
+    // add a texture for the shader, e.g. a gradient lookup or a mask, see `TextureBinder`
+    // for the group & bindings it is declared at
+    pub fn with_texture(mut self, img: image::DynamicImage, sampler: SamplerConfig) -> Self {
+        self.textures.push((img, sampler));
+        self
+    }
+
+    // the vertex buffers will be updated in place with `queue.write_buffer`
+    pub fn with_dynamic_vertices(mut self) -> Self {
+        self.render_options.dynamic_vertices = true;
+        self
+    }
+
+    // write `reference` in the stencil buffer where the object is drawn, e.g. a portal
+    pub fn as_stencil_mask(mut self, reference: u32) -> Self {
+        self.render_options.stencil = Some(StencilMode::Mask(reference));
+        self
+    }
+
+    // only draw the object where a mask added before it wrote `reference`
+    pub fn stencil_test(mut self, reference: u32) -> Self {
+        self.render_options.stencil = Some(StencilMode::Test(reference));
+        self
+    }
+
+    // derive the coverage of the pixels from the alpha, e.g. for foliage
+    pub fn with_alpha_to_coverage(mut self) -> Self {
+        self.render_options.alpha_to_coverage = true;
+        self
+    }
+
+    // leave the object out of the shadow map, e.g. for a ground that only receives shadows
+    pub fn without_shadows(mut self) -> Self {
+        self.render_options.casts_shadows = false;
+        self
+    }
+
+    // always draw the object, for shaders moving the vertices away from their loaded positions
+    pub fn without_culling(mut self) -> Self {
+        self.render_options.culling = false;
+        self
+    }
+
+    // draw the edges of the triangles instead of the user shader,
+    // works without the line polygon mode so on every backend
+    pub fn as_wireframe(mut self) -> Self {
+        self.render_options.wireframe = true;
+        self.render_options.blend = wgpu::BlendState::ALPHA_BLENDING;
+        self
+    }
+
+    // draw the texture of the material, ignoring the light and the shader of the object,
+    // e.g. for gizmos or emissive signs. Objects without material, like obj files without
+    // mtl, are drawn grey & shaded by their normals facing the camera
+    pub fn unlit(mut self) -> Self {
+        self.render_options.unlit = true;
+        self
+    }
+
+    // draw the edges (Line) or the vertices (Point) of the triangles with the rasterizer,
+    // e.g. to debug the topology of a mesh. It is a native only feature, not available on
+    // WebGL, the object is filled with a warning when the adapter lacks it
+    pub fn with_polygon_mode(mut self, polygon_mode: wgpu::PolygonMode) -> Self {
+        self.render_options.polygon_mode = polygon_mode;
+        self
+    }
+
+    // winding order of the front faces, some exporters use clockwise
+    pub fn with_front_face(mut self, front_face: wgpu::FrontFace) -> Self {
+        self.render_options.front_face = front_face;
+        self
+    }
 }
 
+// time between two frames rendered without window, 60 frames per second
+pub const HEADLESS_FRAME_TIME: std::time::Duration = std::time::Duration::from_nanos(16_666_667);
+// size of the frame saved by `Lens::render_to_image`
+pub const IMAGE_SIZE: (u32, u32) = (800, 600);
+
+type UpdateCallback = Box<dyn FnMut(&mut Scene, std::time::Duration)>;
+type ResizeCallback = Box<dyn FnMut(u32, u32)>;
+
 pub struct Lens<'a> {
-    // add a light
-    // add a camera
-    // add meshes
-    lens_objects: Vec<LensObject<'a>>,
+    // objects of each named scene, the first scene receives the objects of `add_object`
+    lens_scenes: Vec<(String, Vec<LensObject<'a>>)>,
+    active_scene: String,
+    scene_config: SceneConfig,
+    // called each frame before rendering
+    update_callback: Option<UpdateCallback>,
+    // called when the window is resized
+    resize_callback: Option<ResizeCallback>,
+}
+
+impl<'a> Default for Lens<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<'a> Lens<'a> {
     pub fn new() -> Lens<'a> {
         Lens {
-            lens_objects: Vec::new(),
+            lens_scenes: vec![(DEFAULT_SCENE.to_string(), Vec::new())],
+            active_scene: DEFAULT_SCENE.to_string(),
+            scene_config: SceneConfig::default(),
+            update_callback: None,
+            resize_callback: None,
+        }
+    }
+
+    pub fn with_line_width(mut self, line_width: f32) -> Self {
+        self.scene_config.line_width = line_width;
+        self
+    }
+
+    // speed in world units per second, sensitivity in radians per pixel of mouse motion
+    // and zoom speed in world units per pixel scrolled, both scaled by the frame time.
+    // The defaults are 4.0, 0.4 & 1.6
+    pub fn with_camera_controls(mut self, speed: f32, sensitivity: f32, zoom_speed: f32) -> Self {
+        self.scene_config.camera_controls = (speed, sensitivity, zoom_speed);
+        self
+    }
+
+    // start the camera at `eye` looking at `target`
+    pub fn with_camera_origin(mut self, eye: [f32; 3], target: [f32; 3]) -> Self {
+        self.scene_config.camera_origin = Some((eye, target));
+        self
+    }
+
+    // vertical field of view in degrees, clamped between 10 and 120,
+    // `Scene::camera_mut().set_fov` changes it while running
+    pub fn with_fov(mut self, degrees: f32) -> Self {
+        self.scene_config.fov = degrees;
+        self
+    }
+
+    // background of the scene, channels are clamped to [0, 1]
+    pub fn with_clear_color(mut self, r: f64, g: f64, b: f64, a: f64) -> Self {
+        self.scene_config.clear_color = clear_color(r, g, b, a);
+        self
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.scene_config.title = title.to_string();
+        self
+    }
+
+    // inner size of the window in physical pixels, also the size of `render_to_image`
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.scene_config.size = Some((width, height));
+        self
+    }
+
+    // lights kept in each cluster of the view, clamped between 1 and MAX_LIGHTS,
+    // DEFAULT_MAX_LIGHTS_PER_CLUSTER by default. The lights over it are left out of the
+    // cluster in the order they were added
+    pub fn with_max_lights_per_cluster(mut self, count: usize) -> Self {
+        self.scene_config.max_lights_per_cluster = count.clamp(1, light::MAX_LIGHTS);
+        self
+    }
+
+    // draw the depth of the objects seen from the first light in a `size` x `size` map, bound
+    // with the lights for the shaders to compute their shadows. 2048 is a good start
+    pub fn with_shadows(mut self, size: u32) -> Self {
+        self.scene_config.shadow_map_size = Some(size);
+        self
+    }
+
+    // present mode of the surface, Fifo (vsync) by default, see `Scene::set_present_mode`
+    pub fn with_present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.scene_config.present_mode = present_mode;
+        self
+    }
+
+    // key toggling borderless fullscreen on the current monitor, F11 by default, None
+    // disables it. It takes over the debug toggle bound to the same key, Escape keeps its action
+    pub fn with_fullscreen_key(mut self, key: Option<VirtualKeyCode>) -> Self {
+        self.scene_config.fullscreen_key = key;
+        self
+    }
+
+    // key toggling the stats overlay, F3 by default, None disables it
+    pub fn with_stats_key(mut self, key: Option<VirtualKeyCode>) -> Self {
+        self.scene_config.stats_key = key;
+        self
+    }
+
+    // key toggling the view of the depth buffer, F4 by default, None disables it
+    pub fn with_depth_view_key(mut self, key: Option<VirtualKeyCode>) -> Self {
+        self.scene_config.depth_view_key = key;
+        self
+    }
+
+    // key toggling the bounding boxes of the objects, B by default, None disables it
+    pub fn with_bounds_key(mut self, key: Option<VirtualKeyCode>) -> Self {
+        self.scene_config.bounds_key = key;
+        self
+    }
+
+    // key grabbing & releasing the cursor, G by default, None disables it
+    pub fn with_cursor_grab_key(mut self, key: Option<VirtualKeyCode>) -> Self {
+        self.scene_config.cursor_grab_key = key;
+        self
+    }
+
+    // draw a cubemap behind the objects in place of the clear color, the faces are square
+    // images of the same size given in the +X, -X, +Y, -Y, +Z, -Z order
+    pub fn with_skybox<P: AsRef<std::path::Path>>(mut self, paths: [P; 6]) -> Self {
+        self.scene_config.skybox = Some(paths.map(|path| path.as_ref().to_path_buf()));
+        self
+    }
+
+    // smooth the edges of the triangles with `sample_count` samples per pixel, 4 is supported
+    // everywhere and other counts fall back to 1. TAA, the aux buffers & the depth debug
+    // view are not available with MSAA
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.scene_config.sample_count = sample_count;
+        self
+    }
+
+    // draw an image with alpha blending on top of the scene, e.g. a logo or a crosshair.
+    // `rect` is x, y, width, height in pixels from the top-left corner of the window.
+    pub fn add_overlay(&mut self, img: image::DynamicImage, rect: [f32; 4]) {
+        self.scene_config.overlays.push((img, rect));
+    }
+
+    // frame the camera on an object before the first frame, indexed in the order it was added
+    pub fn look_at_object(&mut self, id: ObjectId) {
+        self.scene_config.look_at_object = Some(id);
+    }
+
+    // draw the bounding box of each object with a color of its own, B toggles it while running
+    pub fn show_bounds(&mut self, show_bounds: bool) {
+        self.scene_config.show_bounds = show_bounds;
+    }
+
+    // add a point light, `range` 0 for an infinite one; the added lights replace the default
+    // rotating one, up to MAX_LIGHTS, the others are ignored
+    pub fn add_light(&mut self, position: [f32; 3], color: [f32; 3], range: f32) {
+        self.push_light(light::LightUniform::new(
+            light::LightKind::Point { position },
+            color,
+            range,
+        ));
+    }
+
+    // add a light shining toward `direction` from infinitely far, like the sun
+    pub fn add_directional_light(&mut self, direction: [f32; 3], color: [f32; 3]) {
+        self.push_light(light::LightUniform::new(
+            light::LightKind::Directional { direction },
+            color,
+            0.0,
+        ));
+    }
+
+    // add a light shining a cone from `position` toward `direction`, full inside `inner_angle`
+    // and fading out up to `outer_angle`, in degrees from the direction
+    pub fn add_spot_light(
+        &mut self,
+        position: [f32; 3],
+        direction: [f32; 3],
+        inner_angle: f32,
+        outer_angle: f32,
+        color: [f32; 3],
+    ) {
+        self.push_light(light::LightUniform::new(
+            light::LightKind::Spot {
+                position,
+                direction,
+                inner_angle,
+                outer_angle,
+            },
+            color,
+            0.0,
+        ));
+    }
+
+    // make the randomized effects reproducible across runs, e.g. the camera shake
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.scene_config.random_seed = seed;
+    }
+
+    // ambient light color, 0.1 gray by default
+    pub fn set_ambient(&mut self, ambient: [f32; 3]) {
+        self.scene_config.ambient = ambient;
+    }
+
+    // depth bias of the objects drawn in the shadow map against the shadow acne, `constant` in
+    // depth units & `slope` scaled by the slope of the surface seen from the light, (2, 2.0) by
+    // default. Raise it when lit surfaces shadow themselves, lower it when the shadows detach
+    // from their casters
+    pub fn set_shadow_bias(&mut self, constant: i32, slope: f32) {
+        self.scene_config.shadow_bias = (constant, slope);
+    }
+
+    fn push_light(&mut self, light_uniform: light::LightUniform) {
+        if self.scene_config.lights.len() < light::MAX_LIGHTS {
+            self.scene_config.lights.push(light_uniform);
+        } else {
+            log::warn!(
+                "more than {} lights, the light is ignored",
+                light::MAX_LIGHTS
+            );
+        }
+    }
+
+    // smooth the edges of the opaque objects by accumulating jittered frames,
+    // not available when an object uses the stencil buffer
+    pub fn set_taa(&mut self, enabled: bool) {
+        self.scene_config.taa = enabled;
+    }
+
+    // polygon mode of all the objects without one of their own, see
+    // `LensObject::with_polygon_mode`, e.g. Line to see the topology of the whole scene
+    pub fn set_polygon_mode(&mut self, polygon_mode: wgpu::PolygonMode) {
+        self.scene_config.polygon_mode = polygon_mode;
+    }
+
+    // draw a top-down view of the opaque objects in `rect` (x, y, width, height in pixels
+    // from the top-left corner), seen from `height` above the camera, below the far plane
+    pub fn enable_minimap(&mut self, rect: [f32; 4], height: f32) {
+        self.scene_config.minimap = Some((rect, height));
+    }
+
+    // write the world normal & position of the opaque objects in textures
+    // exposed by `Scene::aux_normal_view` & `Scene::aux_position_view`
+    pub fn enable_aux_buffers(&mut self, enabled: bool) {
+        self.scene_config.aux_buffers = enabled;
+    }
+
+    // add a water plane over `rect` (x, z, width, depth in world units) at `params.height`
+    pub fn add_water(&mut self, rect: [f32; 4], params: WaterParams) -> ObjectId {
+        let mut object = Object::plane(rect[2], rect[3], water::SUBDIVISIONS);
+        let center = [
+            rect[0] + rect[2] / 2.0,
+            params.height,
+            rect[1] + rect[3] / 2.0,
+        ];
+        for position in object.models[0].mesh.positions.chunks_exact_mut(3) {
+            for axis in 0..3 {
+                position[axis] += center[axis];
+            }
+        }
+        self.add_object(LensObject::new(object, "").as_water(params))
+    }
+
+    // the id reaches the object through the `Scene` while running
+    pub fn add_object(&mut self, lens_object: LensObject<'a>) -> ObjectId {
+        let objects = &mut self.lens_scenes[0].1;
+        objects.push(lens_object);
+        ObjectId::new(0, objects.len() - 1)
+    }
+
+    // objects of a scene that can be switched to, the GPU context is shared. Their ids reach
+    // them whichever scene is active
+    pub fn add_scene(&mut self, name: &str, lens_objects: Vec<LensObject<'a>>) -> Vec<ObjectId> {
+        let scene = match self
+            .lens_scenes
+            .iter()
+            .position(|(scene_name, _)| scene_name == name)
+        {
+            Some(index) => index,
+            None => {
+                self.lens_scenes.push((name.to_string(), Vec::new()));
+                self.lens_scenes.len() - 1
+            }
+        };
+        let objects = &mut self.lens_scenes[scene].1;
+        let first = objects.len();
+        objects.extend(lens_objects);
+        (first..objects.len())
+            .map(|index| ObjectId::new(scene, index))
+            .collect()
+    }
+
+    // scene drawn when the window opens, use `Scene::activate_scene` to switch at runtime
+    pub fn activate_scene(&mut self, name: &str) {
+        self.active_scene = name.to_string();
+    }
+
+    pub fn on_update<F: FnMut(&mut Scene, std::time::Duration) + 'static>(&mut self, callback: F) {
+        self.update_callback = Some(Box::new(callback));
+    }
+
+    pub fn on_resize<F: FnMut(u32, u32) + 'static>(&mut self, callback: F) {
+        self.resize_callback = Some(Box::new(callback));
+    }
+
+    // format of the frames rendered by `run_headless` & `render_to_image`, Rgba8UnormSrgb
+    // by default. Only the 8 bits RGBA & BGRA formats can be captured
+    pub fn with_headless_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.scene_config.headless_format = format;
+        self
+    }
+
+    // render `frames` frames of `width` x `height` pixels without window nor event loop and
+    // return them, e.g. for image regression tests in CI. The update callback & the scene
+    // get a fixed frame time of HEADLESS_FRAME_TIME, so each run gives the same frames.
+    // The objects are moved to the rendered scene
+    pub fn run_headless(
+        &mut self,
+        width: u32,
+        height: u32,
+        frames: u32,
+    ) -> anyhow::Result<Vec<image::RgbaImage>> {
+        let _ = env_logger::try_init();
+        if width == 0 || height == 0 {
+            anyhow::bail!(
+                "headless frames of {}x{} pixels can't be rendered",
+                width,
+                height
+            );
+        }
+
+        let mut scene = pollster::block_on(Scene::new(
+            None,
+            winit::dpi::PhysicalSize::new(width, height),
+            &mut self.lens_scenes,
+            &self.active_scene,
+            &self.scene_config,
+        ))?;
+        let mut images = Vec::with_capacity(frames as usize);
+        for _ in 0..frames {
+            if let Some(callback) = self.update_callback.as_mut() {
+                callback(&mut scene, HEADLESS_FRAME_TIME);
+            }
+            scene.update(HEADLESS_FRAME_TIME);
+            images.push(scene.capture_frame()?);
         }
+        Ok(images)
     }
 
-    pub fn add_object(&mut self, lens_object: LensObject<'a>) {
-        self.lens_objects.push(lens_object);
+    // render a single headless frame of the `with_size` size, IMAGE_SIZE by default, and
+    // save it, the image format follows the extension of `path`
+    pub fn render_to_image<P: AsRef<std::path::Path>>(&mut self, path: P) -> anyhow::Result<()> {
+        let (width, height) = self.scene_config.size.unwrap_or(IMAGE_SIZE);
+        for image in self.run_headless(width, height, 1)? {
+            image.save(path.as_ref())?;
+        }
+        Ok(())
     }
 
-    pub fn run(&mut self) {
-        env_logger::init();
+    // errors while creating the scene, e.g. a texture that can't be uploaded, are returned,
+    // the function doesn't return once the scene runs
+    pub fn run(&mut self) -> anyhow::Result<()> {
+        // the logger may already be set by `run_headless`
+        let _ = env_logger::try_init();
         let mut last_render_time = std::time::Instant::now();
 
         let event_loop = EventLoop::new();
-        let window = WindowBuilder::new().build(&event_loop).unwrap();
+        let mut window_builder = WindowBuilder::new().with_title(&self.scene_config.title);
+        if let Some((width, height)) = self.scene_config.size {
+            window_builder =
+                window_builder.with_inner_size(winit::dpi::PhysicalSize::new(width, height));
+        }
+        let window = window_builder.build(&event_loop)?;
         // Scene::new uses async code, so we're going to wait for it to finish
-        let mut scene = pollster::block_on(Scene::new(&window, &mut self.lens_objects));
+        let mut scene = pollster::block_on(Scene::new(
+            Some(&window),
+            window.inner_size(),
+            &mut self.lens_scenes,
+            &self.active_scene,
+            &self.scene_config,
+        ))?;
+        let mut update_callback = self.update_callback.take();
+        let mut resize_callback = self.resize_callback.take();
+        let mut window_grabbed = false;
+        let fullscreen_key = self.scene_config.fullscreen_key;
+        let stats_key = self.scene_config.stats_key;
+        let depth_view_key = self.scene_config.depth_view_key;
+        let bounds_key = self.scene_config.bounds_key;
+        let cursor_grab_key = self.scene_config.cursor_grab_key;
 
         event_loop.run(move |event, _, control_flow| {
             *control_flow = ControlFlow::Poll;
@@ -282,6 +2135,16 @@ impl<'a> Lens<'a> {
                     window_id,
                 } if window_id == window.id() => {
                     match event {
+                        // Escape releases the cursor before closing the window
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(VirtualKeyCode::Escape),
+                                    ..
+                                },
+                            ..
+                        } if scene.cursor_grabbed() => scene.set_cursor_grab(false),
                         WindowEvent::CloseRequested
                         | WindowEvent::KeyboardInput {
                             input:
@@ -292,12 +2155,49 @@ impl<'a> Lens<'a> {
                                 },
                             ..
                         } => *control_flow = ControlFlow::Exit,
+                        // the user-configured fullscreen key is checked before the debug
+                        // toggles, the surface follows through the Resized event
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(key),
+                                    ..
+                                },
+                            ..
+                        } => {
+                            let key = Some(*key);
+                            if key == fullscreen_key {
+                                window.set_fullscreen(match window.fullscreen() {
+                                    Some(_) => None,
+                                    None => Some(Fullscreen::Borderless(None)),
+                                });
+                            } else if key == stats_key {
+                                scene.toggle_stats();
+                            } else if key == depth_view_key {
+                                scene.toggle_depth_view();
+                            } else if key == bounds_key {
+                                scene.toggle_bounds();
+                            } else if key == cursor_grab_key {
+                                scene.set_cursor_grab(!scene.cursor_grabbed());
+                            }
+                        }
+                        WindowEvent::CursorMoved { position, .. } => {
+                            scene.cursor_position = Some(*position);
+                        }
+                        WindowEvent::CursorLeft { .. } => scene.cursor_position = None,
                         WindowEvent::Resized(physical_size) => {
                             scene.resize(*physical_size);
+                            if let Some(callback) = resize_callback.as_mut() {
+                                callback(physical_size.width, physical_size.height);
+                            }
                         }
                         WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
                             // new_inner_size is &&mut so we have to dereference it twice
                             scene.resize(**new_inner_size);
+                            if let Some(callback) = resize_callback.as_mut() {
+                                callback(new_inner_size.width, new_inner_size.height);
+                            }
                         }
                         _ => {}
                     }
@@ -306,6 +2206,9 @@ impl<'a> Lens<'a> {
                     let now = std::time::Instant::now();
                     let dt = now - last_render_time;
                     last_render_time = now;
+                    if let Some(callback) = update_callback.as_mut() {
+                        callback(&mut scene, dt);
+                    }
                     scene.update(dt);
                     match scene.render() {
                         Ok(_) => {}
@@ -318,6 +2221,15 @@ impl<'a> Lens<'a> {
                     }
                 }
                 Event::MainEventsCleared => {
+                    if scene.cursor_grabbed() != window_grabbed {
+                        window_grabbed = scene.cursor_grabbed();
+                        // grabbing is not supported on every platform
+                        if let Err(e) = window.set_cursor_grab(window_grabbed) {
+                            log::warn!("failed to grab the cursor : {:?}", e);
+                        }
+                        window.set_cursor_visible(!window_grabbed);
+                    }
+
                     // RedrawRequested will only trigger once, unless we manually
                     // request it.
                     window.request_redraw();
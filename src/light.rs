@@ -1,3 +1,6 @@
+use crate::camera::Camera;
+use crate::light_clusters::LightClusters;
+use crate::shadow::ShadowMap;
 use cgmath::prelude::*;
 use wgpu::util::DeviceExt;
 
@@ -5,69 +8,500 @@ use wgpu::util::DeviceExt;
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct LightUniform {
     pub position: [f32; 3],
-    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
-    pub _padding: u32,
+    // LightKind of the light, fills the 16 byte spacing after the position
+    pub kind: u32,
     pub color: [f32; 3],
+    // distance where the light fades out, 0 for an infinite range
+    pub range: f32,
+    // normalized direction the light shines toward, unused by point lights
+    pub direction: [f32; 3],
+    // cosines of the cone angles of a spot light, full light inside the inner one
+    pub cos_inner: f32,
+    pub cos_outer: f32,
+    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
+    pub _padding: [u32; 3],
+}
+
+pub const POINT_LIGHT: u32 = 0;
+pub const DIRECTIONAL_LIGHT: u32 = 1;
+pub const SPOT_LIGHT: u32 = 2;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LightKind {
+    // lights around it, fading with the distance
+    Point {
+        position: [f32; 3],
+    },
+    // lights everything from the same direction, like the sun
+    Directional {
+        direction: [f32; 3],
+    },
+    // lights a cone around `direction`, angles in degrees from its axis
+    Spot {
+        position: [f32; 3],
+        direction: [f32; 3],
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+// straight down without a direction
+fn normalize_direction(direction: [f32; 3]) -> [f32; 3] {
+    let direction = cgmath::Vector3::from(direction);
+    if direction.magnitude2() > 0.0 {
+        direction.normalize().into()
+    } else {
+        (-cgmath::Vector3::unit_y()).into()
+    }
+}
+
+impl LightUniform {
+    // `range` doesn't apply to directional lights, 0 for an infinite one
+    pub fn new(kind: LightKind, color: [f32; 3], range: f32) -> Self {
+        let mut light_uniform = LightUniform {
+            position: [0.0; 3],
+            kind: POINT_LIGHT,
+            color,
+            range: range.max(0.0),
+            direction: [0.0, -1.0, 0.0],
+            cos_inner: 1.0,
+            cos_outer: 1.0,
+            _padding: [0; 3],
+        };
+        match kind {
+            LightKind::Point { position } => light_uniform.position = position,
+            LightKind::Directional { direction } => {
+                light_uniform.kind = DIRECTIONAL_LIGHT;
+                light_uniform.direction = normalize_direction(direction);
+            }
+            LightKind::Spot {
+                position,
+                direction,
+                inner_angle,
+                outer_angle,
+            } => {
+                let outer_angle = outer_angle.clamp(0.0, 180.0);
+                // the inner cone can't be wider than the outer one
+                let inner_angle = inner_angle.clamp(0.0, outer_angle);
+                light_uniform.kind = SPOT_LIGHT;
+                light_uniform.position = position;
+                light_uniform.direction = normalize_direction(direction);
+                light_uniform.cos_inner = inner_angle.to_radians().cos();
+                light_uniform.cos_outer = outer_angle.to_radians().cos();
+            }
+        }
+        light_uniform
+    }
+
+    pub fn kind(&self) -> LightKind {
+        match self.kind {
+            DIRECTIONAL_LIGHT => LightKind::Directional {
+                direction: self.direction,
+            },
+            SPOT_LIGHT => LightKind::Spot {
+                position: self.position,
+                direction: self.direction,
+                inner_angle: self.cos_inner.acos().to_degrees(),
+                outer_angle: self.cos_outer.acos().to_degrees(),
+            },
+            _ => LightKind::Point {
+                position: self.position,
+            },
+        }
+    }
 }
 
+// lights of a scene, the storage buffer of the lights is allocated for all of them
+pub const MAX_LIGHTS: usize = 1024;
+
+/// Start of binding 1 of the light group, a storage buffer where the `count` lights follow
+/// this header, readable by the fragment shaders. Binding 0 is the first light alone.
+/// `kind` is 0 for a point light, 1 for a directional one & 2 for a spot light, `direction`
+/// is the normalized direction the directional & spot lights shine toward. The light fades
+/// to zero at its range, 0 for an infinite one :
+///
+/// ```wgsl
+/// struct Light {
+///     position: vec3<f32>;
+///     kind: u32;
+///     color: vec3<f32>;
+///     range: f32;
+///     direction: vec3<f32>;
+///     cos_inner: f32;
+///     cos_outer: f32;
+/// };
+///
+/// [[block]]
+/// struct Lights {
+///     count: u32;
+///     ambient: vec3<f32>;
+///     lights: array<Light>;
+/// };
+/// [[group(1), binding(1)]]
+/// var<storage, read> lights: Lights;
+///
+/// // windowed inverse-square falloff
+/// fn attenuation(distance: f32, range: f32) -> f32 {
+///     let falloff = 1.0 / max(distance * distance, 0.0001);
+///     if (range <= 0.0) {
+///         return falloff;
+///     }
+///     let window = clamp(1.0 - pow(distance / range, 4.0), 0.0, 1.0);
+///     return falloff * window * window;
+/// }
+///
+/// fn shade(world_position: vec3<f32>, world_normal: vec3<f32>) -> vec3<f32> {
+///     // multiplied by the object color with the other lights
+///     var color = lights.ambient;
+///     for (var i = 0u; i < lights.count; i = i + 1u) {
+///         let light = lights.lights[i];
+///         var light_dir = -light.direction;
+///         var intensity = 1.0;
+///         if (light.kind != 1u) {
+///             light_dir = normalize(light.position - world_position);
+///             intensity = attenuation(distance(light.position, world_position), light.range);
+///         }
+///         if (light.kind == 2u) {
+///             // smoothstep between the cone cosines, the WGSL builtin isn't parsed by naga 0.7
+///             let cos_angle = dot(-light_dir, light.direction);
+///             let t = clamp(
+///                 (cos_angle - light.cos_outer) / max(light.cos_inner - light.cos_outer, 0.0001),
+///                 0.0,
+///                 1.0,
+///             );
+///             intensity = intensity * t * t * (3.0 - 2.0 * t);
+///         }
+///         color = color + light.color * intensity * max(dot(world_normal, light_dir), 0.0);
+///     }
+///     return color;
+/// }
+/// ```
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightsUniform {
+    pub count: u32,
+    pub _padding: [u32; 3],
+    // added to the object color before the lights
+    pub ambient: [f32; 3],
+    pub _ambient_padding: u32,
+}
+
+// ambient strength of the usual lit shaders
+pub const DEFAULT_AMBIENT: [f32; 3] = [0.1, 0.1, 0.1];
+
+// The first light is animated & edited by the setters, the others stay as they were added.
 pub struct Light {
-    light_uniform: LightUniform,
+    lights_uniform: LightsUniform,
+    lights: Vec<LightUniform>,
+    // binding 0, a copy of the first light
+    first_light_buffer: wgpu::Buffer,
+    // binding 1, the header followed by the lights
     light_buffer: wgpu::Buffer,
+    // hue rotation speed in degrees per second, 0 when disabled
+    hue_speed: f32,
+    // rotation around the Y axis in degrees per second, 0 when disabled
+    rotation_speed: f32,
+    // lights of each cluster of the view, bound after the shadow map
+    clusters: LightClusters,
     pub bind_group_layout: wgpu::BindGroupLayout,
     pub bind_group: wgpu::BindGroup,
 }
 
+// h in degrees, s & v in [0, 1]
+pub fn rgb_to_hsv(rgb: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb;
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+    [hue, saturation, max]
+}
+
+pub fn hsv_to_rgb(hsv: [f32; 3]) -> [f32; 3] {
+    let [hue, saturation, value] = hsv;
+    let chroma = value * saturation;
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+
+    [r + m, g + m, b + m]
+}
+
 impl Light {
-    pub fn bind(device: &wgpu::Device, light_uniform: LightUniform) -> Self {
+    // the shadow map of the first light is bound after the lights, then the light clusters
+    pub fn bind(
+        device: &wgpu::Device,
+        light_uniform: LightUniform,
+        shadow_map: &ShadowMap,
+        max_lights_per_cluster: usize,
+    ) -> Self {
+        let lights_uniform = LightsUniform {
+            count: 1,
+            _padding: [0; 3],
+            ambient: DEFAULT_AMBIENT,
+            _ambient_padding: 0,
+        };
+
         // We'll want to update our lights position, so we use COPY_DST
-        let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        let first_light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Light VB"),
             contents: bytemuck::cast_slice(&[light_uniform]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
+        // room for MAX_LIGHTS after the header, the unused lights stay zeroed
+        let mut contents = bytemuck::bytes_of(&lights_uniform).to_vec();
+        contents.extend_from_slice(bytemuck::bytes_of(&light_uniform));
+        contents.resize(
+            std::mem::size_of::<LightsUniform>() + MAX_LIGHTS * std::mem::size_of::<LightUniform>(),
+            0,
+        );
+        let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Lights Buffer"),
+            contents: &contents,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let clusters = LightClusters::new(device, &light_buffer, max_lights_per_cluster);
 
+        let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        // storage buffers aren't readable by vertex shaders on every backend
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+            entries: &[
+                uniform_entry(0),
+                storage_entry(1),
+                uniform_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: true,
+                        filtering: true,
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+                uniform_entry(5),
+                storage_entry(6),
+            ],
             label: None,
         });
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: light_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: first_light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: shadow_map.uniform_buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&shadow_map.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(&shadow_map.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: clusters.uniform_buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: clusters.cluster_buffer().as_entire_binding(),
+                },
+            ],
             label: None,
         });
 
         Light {
-            light_uniform,
+            lights_uniform,
+            lights: vec![light_uniform],
+            first_light_buffer,
             light_buffer,
+            hue_speed: 0.0,
+            rotation_speed: 60.0,
+            clusters,
             bind_group_layout,
             bind_group,
         }
     }
 
-    pub fn update(&mut self, queue: &wgpu::Queue, dt: std::time::Duration) {
-        let old_position: cgmath::Vector3<_> = self.light_uniform.position.into();
-        self.light_uniform.position = (cgmath::Quaternion::from_axis_angle(
-            (0.0, 1.0, 0.0).into(),
-            cgmath::Deg(60.0 * dt.as_secs_f32()),
-        ) * old_position)
-            .into();
+    // index of the new light, None when MAX_LIGHTS are already set
+    pub fn add(&mut self, light_uniform: LightUniform) -> Option<usize> {
+        let index = self.lights.len();
+        if index == MAX_LIGHTS {
+            return None;
+        }
+        self.lights.push(light_uniform);
+        self.lights_uniform.count += 1;
+        Some(index)
+    }
+
+    pub fn lights(&self) -> &[LightUniform] {
+        &self.lights
+    }
+
+    // written to the GPU on the next update
+    pub fn lights_mut(&mut self) -> &mut [LightUniform] {
+        &mut self.lights
+    }
+
+    // written to the GPU right away, disable the rotation to keep the position
+    pub fn set_position(&mut self, queue: &wgpu::Queue, position: [f32; 3]) {
+        self.lights[0].position = position;
+        self.write(queue);
+    }
+
+    pub fn position(&self) -> [f32; 3] {
+        self.lights[0].position
+    }
+
+    // written to the GPU right away, disable the hue cycle to keep the color
+    pub fn set_color(&mut self, queue: &wgpu::Queue, color: [f32; 3]) {
+        self.lights[0].color = color;
+        self.write(queue);
+    }
+
+    pub fn color(&self) -> [f32; 3] {
+        self.lights[0].color
+    }
+
+    // written to the GPU right away
+    pub fn set_ambient(&mut self, queue: &wgpu::Queue, ambient: [f32; 3]) {
+        self.lights_uniform.ambient = ambient;
+        self.write(queue);
+    }
+
+    pub fn ambient(&self) -> [f32; 3] {
+        self.lights_uniform.ambient
+    }
+
+    // lights kept in each cluster, the others reaching it are left out
+    pub fn max_lights_per_cluster(&self) -> usize {
+        self.clusters.max_lights_per_cluster()
+    }
+
+    // record the pass assigning the lights to the clusters of the view of `camera`, before the
+    // passes drawing the lit objects
+    pub fn assign_clusters(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        camera: &Camera,
+    ) {
+        self.clusters.assign(queue, encoder, camera);
+    }
+
+    // turn the light around the Y axis, 60 degrees per second by default, 0 disables it
+    pub fn set_rotation_speed(&mut self, speed: f32) {
+        self.rotation_speed = speed;
+    }
+
+    fn write(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.first_light_buffer,
+            0,
+            bytemuck::cast_slice(&self.lights[..1]),
+        );
         queue.write_buffer(
             &self.light_buffer,
             0,
-            bytemuck::cast_slice(&[self.light_uniform]),
+            bytemuck::cast_slice(&[self.lights_uniform]),
         );
+        queue.write_buffer(
+            &self.light_buffer,
+            std::mem::size_of::<LightsUniform>() as u64,
+            bytemuck::cast_slice(&self.lights),
+        );
+    }
+
+    // the light doesn't reach fragments further than `range`, 0 disables the cutoff
+    pub fn set_range(&mut self, range: f32) {
+        self.lights[0].range = range.max(0.0);
+    }
+
+    pub fn range(&self) -> f32 {
+        self.lights[0].range
+    }
+
+    // animate the color around the hue wheel, a speed of 0 disables it
+    pub fn cycle_hue(&mut self, speed: f32) {
+        self.hue_speed = speed;
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, dt: std::time::Duration) {
+        if self.hue_speed != 0.0 {
+            let [hue, saturation, value] = rgb_to_hsv(self.lights[0].color);
+            self.lights[0].color =
+                hsv_to_rgb([hue + self.hue_speed * dt.as_secs_f32(), saturation, value]);
+        }
+
+        if self.rotation_speed != 0.0 {
+            let rotation = cgmath::Quaternion::from_axis_angle(
+                (0.0, 1.0, 0.0).into(),
+                cgmath::Deg(self.rotation_speed * dt.as_secs_f32()),
+            );
+            let light = &mut self.lights[0];
+            let old_position: cgmath::Vector3<_> = light.position.into();
+            light.position = (rotation * old_position).into();
+            let old_direction: cgmath::Vector3<_> = light.direction.into();
+            light.direction = (rotation * old_direction).into();
+        }
+        self.write(queue);
     }
 }
@@ -0,0 +1,213 @@
+use crate::camera::Camera;
+use crate::light::MAX_LIGHTS;
+use cgmath::prelude::*;
+use wgpu::util::DeviceExt;
+
+// Lights reaching each cluster of the view of the active camera, assigned by a compute pass
+// before the main pass. The view is divided in tiles on the screen and in depth slices growing
+// exponentially from the near to the far plane, point & spot lights are bounded by the sphere
+// of their range. The clusters are bound with the lights, lit shaders prepend
+// `CLUSTER_LIGHTS_WGSL` to only iterate the lights of the cluster of their fragment.
+
+// clusters along the width, height & depth of the view
+pub const CLUSTER_COUNT: [u32; 3] = [16, 9, 24];
+
+// the lights over the maximum are left out of the cluster, in the order they were added
+pub const DEFAULT_MAX_LIGHTS_PER_CLUSTER: usize = 64;
+
+/// WGSL declaring the clusters at bindings 5 & 6 of the light group, to prepend to a lit
+/// shader once LIGHT_GROUP is replaced by the index of the light group. Fragments outside of
+/// the clustered view iterate all the lights :
+///
+/// ```wgsl
+/// let cluster = light_cluster(world_position);
+/// for (var i = 0u; i < cluster_light_count(cluster, lights.count); i = i + 1u) {
+///     let light = lights.lights[cluster_light(cluster, i)];
+///     ...
+/// }
+/// ```
+pub const CLUSTER_LIGHTS_WGSL: &str = include_str!("shader/cluster_lights.wgsl");
+
+const WORKGROUP_SIZE: u32 = 64;
+
+// binding 5 of the light group
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightClustersUniform {
+    pub view: [[f32; 4]; 4],
+    pub view_proj: [[f32; 4]; 4],
+    pub inverse_projection: [[f32; 4]; 4],
+    // clusters along x, y & z, max lights per cluster in w
+    pub size: [u32; 4],
+    pub near: f32,
+    pub far: f32,
+    pub _padding: [u32; 2],
+}
+
+pub struct LightClusters {
+    uniform: LightClustersUniform,
+    uniform_buffer: wgpu::Buffer,
+    // binding 6 of the light group, for each cluster its light count followed by the
+    // indices of its lights
+    cluster_buffer: wgpu::Buffer,
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+}
+
+impl LightClusters {
+    // `lights_buffer` holds the `LightsUniform` the clusters are assigned from,
+    // `max_lights_per_cluster` is clamped between 1 and MAX_LIGHTS
+    pub fn new(
+        device: &wgpu::Device,
+        lights_buffer: &wgpu::Buffer,
+        max_lights_per_cluster: usize,
+    ) -> Self {
+        let max_lights_per_cluster = max_lights_per_cluster.clamp(1, MAX_LIGHTS) as u32;
+        let [x, y, z] = CLUSTER_COUNT;
+        let uniform = LightClustersUniform {
+            view: cgmath::Matrix4::identity().into(),
+            view_proj: cgmath::Matrix4::identity().into(),
+            inverse_projection: cgmath::Matrix4::identity().into(),
+            size: [x, y, z, max_lights_per_cluster],
+            near: 0.1,
+            far: 100.0,
+            _padding: [0; 2],
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Light Clusters Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        // zeroed, every cluster is empty until the first assignment
+        let cluster_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cluster Lights Buffer"),
+            size: (x * y * z * (max_lights_per_cluster + 1)) as u64
+                * std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                uniform_entry(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("light_clusters_bind_group_layout"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: lights_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: cluster_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("light_clusters_bind_group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Light Clusters Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Light Clusters Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader/light_clusters.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Light Clusters Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cs_main",
+        });
+
+        LightClusters {
+            uniform,
+            uniform_buffer,
+            cluster_buffer,
+            pipeline,
+            bind_group,
+        }
+    }
+
+    pub fn uniform_buffer(&self) -> &wgpu::Buffer {
+        &self.uniform_buffer
+    }
+
+    pub fn cluster_buffer(&self) -> &wgpu::Buffer {
+        &self.cluster_buffer
+    }
+
+    pub fn max_lights_per_cluster(&self) -> usize {
+        self.uniform.size[3] as usize
+    }
+
+    // divide the view of `camera` in clusters & record the pass assigning them their lights
+    pub fn assign(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        camera: &Camera,
+    ) {
+        let projection = camera.projection.calc_matrix();
+        let (near, far) = camera.projection.depth_range();
+        self.uniform.view = camera.view_matrix().into();
+        self.uniform.view_proj = camera.view_proj().into();
+        self.uniform.inverse_projection = projection
+            .invert()
+            .unwrap_or_else(cgmath::Matrix4::identity)
+            .into();
+        self.uniform.near = near;
+        self.uniform.far = far;
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+
+        let [x, y, z] = CLUSTER_COUNT;
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Light Clusters Pass"),
+        });
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_group, &[]);
+        compute_pass.dispatch((x * y * z).div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+}
@@ -0,0 +1,250 @@
+use crate::camera;
+use crate::renderer::Aabb;
+use wgpu::util::DeviceExt;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineInstance {
+    pub start: [f32; 3],
+    pub end: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl LineInstance {
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<LineInstance>() as wgpu::BufferAddress,
+            // each line is a quad of 6 vertices sharing the same instance
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineUniform {
+    viewport: [f32; 2],
+    width: f32,
+    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
+    _padding: u32,
+}
+
+pub struct LineRenderer {
+    line_uniform: LineUniform,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    render_pipeline: wgpu::RenderPipeline,
+    // lines queued for the next frame
+    lines: Vec<LineInstance>,
+    instance_buffer: wgpu::Buffer,
+    instance_capacity: usize,
+    instance_length: usize,
+}
+
+impl LineRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        camera: &camera::Camera,
+        line_width: f32,
+    ) -> Self {
+        let line_uniform = LineUniform {
+            viewport: [config.width as f32, config.height as f32],
+            width: line_width,
+            _padding: 0,
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Line Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[line_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("line_bind_group_layout"),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("line_bind_group"),
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Line Pipeline Layout"),
+                bind_group_layouts: &[&camera.bind_group_layout, &bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Line Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader/line.wgsl").into()),
+        });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Line Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[LineInstance::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format: config.format,
+                    // the edges of the lines are faded with the alpha
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // the quads winding depends on the line direction
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                clamp_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                // lines are tested against the scene but don't hide each other
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+        });
+
+        let instance_capacity = 64;
+        let instance_buffer = LineRenderer::create_instance_buffer(device, instance_capacity);
+
+        LineRenderer {
+            line_uniform,
+            uniform_buffer,
+            bind_group,
+            render_pipeline,
+            lines: Vec::new(),
+            instance_buffer,
+            instance_capacity,
+            instance_length: 0,
+        }
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Line Instance Buffer"),
+            size: (capacity * std::mem::size_of::<LineInstance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn resize(&mut self, queue: &wgpu::Queue, width: u32, height: u32) {
+        self.line_uniform.viewport = [width as f32, height as f32];
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.line_uniform]),
+        );
+    }
+
+    pub fn set_width(&mut self, queue: &wgpu::Queue, line_width: f32) {
+        self.line_uniform.width = line_width;
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.line_uniform]),
+        );
+    }
+
+    pub fn push(&mut self, start: [f32; 3], end: [f32; 3], color: [f32; 4]) {
+        self.lines.push(LineInstance { start, end, color });
+    }
+
+    // the 12 edges of the box
+    pub fn push_aabb(&mut self, aabb: &Aabb, color: [f32; 4]) {
+        let corners = aabb.corners();
+        for index in 0..corners.len() {
+            for axis in 0..3 {
+                // corners differing by one bit share an edge
+                if index & (1 << axis) == 0 {
+                    self.push(corners[index], corners[index | (1 << axis)], color);
+                }
+            }
+        }
+    }
+
+    // upload the queued lines, they are drawn once then cleared
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.lines.len() > self.instance_capacity {
+            self.instance_capacity = self.lines.len().next_power_of_two();
+            self.instance_buffer =
+                LineRenderer::create_instance_buffer(device, self.instance_capacity);
+        }
+        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&self.lines));
+        self.instance_length = self.lines.len();
+        self.lines.clear();
+    }
+}
+
+pub trait DrawLines<'a> {
+    fn draw_lines(&mut self, lines: &'a LineRenderer, camera_bind_group: &'a wgpu::BindGroup);
+}
+
+impl<'a, 'b> DrawLines<'b> for wgpu::RenderPass<'a>
+where
+    'b: 'a,
+{
+    fn draw_lines(&mut self, lines: &'b LineRenderer, camera_bind_group: &'b wgpu::BindGroup) {
+        if lines.instance_length == 0 {
+            return;
+        }
+
+        self.set_pipeline(&lines.render_pipeline);
+        self.set_bind_group(0, camera_bind_group, &[]);
+        self.set_bind_group(1, &lines.bind_group, &[]);
+        self.set_vertex_buffer(0, lines.instance_buffer.slice(..));
+        self.draw(0..6, 0..lines.instance_length as u32);
+    }
+}
@@ -0,0 +1,75 @@
+use crate::{camera, overlay};
+use cgmath::Point3;
+
+// Top-down view of the scene in a corner of the window : an orthographic camera is kept
+// `height` above the scene camera looking down, the opaque objects are drawn again with it
+// in the viewport of `rect` over a dark background.
+
+const BACKGROUND: [u8; 4] = [20, 20, 20, 255];
+
+pub struct Minimap {
+    pub camera: camera::Camera,
+    // x, y, width, height in pixels from the top-left corner of the window
+    rect: [f32; 4],
+    height: f32,
+    pub background: overlay::Overlay,
+}
+
+impl Minimap {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        overlay_renderer: &overlay::OverlayRenderer,
+        rect: [f32; 4],
+        height: f32,
+    ) -> Self {
+        let mut camera = camera::Camera::new(device, config);
+        camera
+            .projection
+            .resize(rect[2].max(1.0) as u32, rect[3].max(1.0) as u32);
+        camera.set_projection_kind(camera::ProjectionKind::Orthographic);
+        camera.projection.set_ortho_height(height);
+
+        let background = overlay::Overlay::new(
+            device,
+            queue,
+            overlay_renderer,
+            &image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+                1,
+                1,
+                image::Rgba(BACKGROUND),
+            )),
+            rect,
+            (config.width, config.height),
+        );
+
+        Minimap {
+            camera,
+            rect,
+            height,
+            background,
+        }
+    }
+
+    // center the view under the scene camera
+    pub fn follow(&mut self, queue: &wgpu::Queue, target: Point3<f32>) {
+        self.camera
+            .set_position(target + cgmath::Vector3::unit_y() * self.height);
+        self.camera.look_at(target);
+        self.camera.update(queue, std::time::Duration::ZERO);
+    }
+
+    pub fn resize(&mut self, queue: &wgpu::Queue, width: u32, height: u32) {
+        self.background.resize(queue, width, height);
+    }
+
+    // x, y, width, height of the rect clamped to the window, None when it is outside
+    pub fn viewport(&self, width: u32, height: u32) -> Option<[f32; 4]> {
+        let x = self.rect[0].clamp(0.0, width as f32);
+        let y = self.rect[1].clamp(0.0, height as f32);
+        let right = (self.rect[0] + self.rect[2]).clamp(0.0, width as f32);
+        let bottom = (self.rect[1] + self.rect[3]).clamp(0.0, height as f32);
+        (right - x >= 1.0 && bottom - y >= 1.0).then_some([x, y, right - x, bottom - y])
+    }
+}
@@ -1,40 +1,822 @@
+use crate::texture::SamplerConfig;
+use cgmath::prelude::*;
+use std::io::BufRead;
 use std::path::Path;
 use tobj::*;
 
 pub struct Object {
     pub models: Vec<Model>,
-    pub textures: Option<Vec<(image::DynamicImage, String, String)>>,
+    pub textures: Option<Vec<MaterialData>>,
+    // morph targets of each model, indexed like `models`
+    pub morph_targets: Vec<Vec<MorphTarget>>,
+    // primitive topology of each model, indexed like `models`, TriangleList by default
+    pub topologies: Vec<wgpu::PrimitiveTopology>,
+}
+
+// textures and sampling options of a material, before being uploaded
+pub struct MaterialData {
+    pub name: String,
+    pub diffuse: image::DynamicImage,
+    pub diffuse_label: String,
+    pub sampler: SamplerConfig,
+    // color of the specular highlight, `Ks` in the mtl file
+    pub specular_color: [f32; 3],
+    // texture tiled `scale` times over the diffuse one to add close-up detail
+    pub detail: Option<(image::DynamicImage, f32)>,
+    // rows, columns & frames per second of a sprite sheet played in the diffuse texture
+    pub atlas: Option<(u32, u32, f32)>,
+    // tangent space normal map, `map_Bump` or `bump` in the mtl file
+    pub normal: Option<image::DynamicImage>,
+}
+
+impl MaterialData {
+    // plain white material used by the meshes without one
+    pub fn fallback() -> Self {
+        MaterialData {
+            name: "fallback".to_string(),
+            diffuse: image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+                1,
+                1,
+                image::Rgba([255, 255, 255, 255]),
+            )),
+            diffuse_label: "fallback_diffuse".to_string(),
+            sampler: SamplerConfig::default(),
+            specular_color: [0.0; 3],
+            detail: None,
+            atlas: None,
+            normal: None,
+        }
+    }
+
+    // sprite sheet of `rows` x `columns` cells played left to right then top to bottom,
+    // the shader samples the current cell with the atlas offset & scale of the material
+    pub fn animated_atlas(
+        texture: image::DynamicImage,
+        rows: u32,
+        columns: u32,
+        frames_per_second: f32,
+    ) -> Self {
+        MaterialData {
+            name: "atlas".to_string(),
+            diffuse: texture,
+            diffuse_label: "atlas_diffuse".to_string(),
+            sampler: SamplerConfig::default(),
+            specular_color: [0.0; 3],
+            detail: None,
+            atlas: Some((rows.max(1), columns.max(1), frames_per_second)),
+            normal: None,
+        }
+    }
+}
+
+// position & normal offsets applied to each vertex of a model, scaled by the target weight
+#[derive(Clone, Debug)]
+pub struct MorphTarget {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+}
+
+#[derive(Debug)]
+pub enum ObjectError {
+    // the obj file couldn't be parsed
+    Obj(tobj::LoadError),
+    // the mtl file of the obj couldn't be found or parsed
+    Material(tobj::LoadError),
+    // a file couldn't be read, e.g. a texture missing from the folder or the resolver
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    // a texture couldn't be decoded
+    Image {
+        path: String,
+        source: image::ImageError,
+    },
+    // the gltf file couldn't be parsed or its buffers couldn't be loaded
+    Gltf {
+        path: String,
+        source: gltf::Error,
+    },
+    // a morph target doesn't fit the model it is added to
+    MorphTarget {
+        model_index: usize,
+        reason: &'static str,
+    },
+}
+
+impl std::fmt::Display for ObjectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjectError::Obj(source) => write!(f, "obj file : {}", source),
+            ObjectError::Material(source) => write!(f, "mtl file : {}", source),
+            ObjectError::Io { path, source } => write!(f, "file {:?} : {}", path, source),
+            ObjectError::Image { path, source } => write!(f, "texture {:?} : {}", path, source),
+            ObjectError::Gltf { path, source } => write!(f, "gltf file {:?} : {}", path, source),
+            ObjectError::MorphTarget {
+                model_index,
+                reason,
+            } => write!(f, "morph target of model {} : {}", model_index, reason),
+        }
+    }
+}
+
+impl std::error::Error for ObjectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjectError::Obj(source) | ObjectError::Material(source) => Some(source),
+            ObjectError::Io { source, .. } => Some(source),
+            ObjectError::Image { source, .. } => Some(source),
+            ObjectError::Gltf { source, .. } => Some(source),
+            ObjectError::MorphTarget { .. } => None,
+        }
+    }
+}
+
+impl ObjectError {
+    // read errors of the image crate are io errors
+    fn from_image(path: &str, error: image::ImageError) -> Self {
+        match error {
+            image::ImageError::IoError(source) => ObjectError::Io {
+                path: path.to_string(),
+                source,
+            },
+            source => ObjectError::Image {
+                path: path.to_string(),
+                source,
+            },
+        }
+    }
+
+    // io & image errors of the gltf crate keep their own variants
+    pub(crate) fn from_gltf(path: &str, error: gltf::Error) -> Self {
+        match error {
+            gltf::Error::Io(source) => ObjectError::Io {
+                path: path.to_string(),
+                source,
+            },
+            gltf::Error::Image(error) => ObjectError::from_image(path, error),
+            source => ObjectError::Gltf {
+                path: path.to_string(),
+                source,
+            },
+        }
+    }
+}
+
+// options applied to the geometry when loading an object
+#[derive(Debug, Default, Copy, Clone)]
+pub struct LoadConfig {
+    // flip the V texture coordinate, for textures appearing upside down
+    pub flip_v: bool,
+    // merge the vertices whose position, normal, texture coordinates & color differ by at
+    // most this distance on each component, the triangles collapsed by the merge are removed
+    pub weld_tolerance: Option<f32>,
+    // sample the textures from their full mip chain, downscaled on the CPU, so that they
+    // don't shimmer in the distance
+    pub generate_mipmaps: bool,
+    // anisotropic filtering of the textures, see `SamplerConfig::anisotropy_clamp`
+    pub anisotropy_clamp: Option<u8>,
+    // addressing of the texture coordinates outside 0..1, e.g. Repeat to tile a texture across
+    // a large quad, ClampToEdge by default
+    pub wrap: wgpu::AddressMode,
+    // read the vertex colors as ambient occlusion baked as a gray level, the vertex colors are
+    // dropped otherwise and the AO is 1.0
+    pub vertex_color_ao: bool,
+}
+
+// spatial hash cell of a position, cells are at least `tolerance` wide so that the vertices
+// to merge are in the same or a neighbouring cell
+fn weld_cell(position: &[f32], cell_size: f32) -> [i64; 3] {
+    [
+        (position[0] / cell_size).floor() as i64,
+        (position[1] / cell_size).floor() as i64,
+        (position[2] / cell_size).floor() as i64,
+    ]
+}
+
+fn weld(mesh: &mut Mesh, tolerance: f32) {
+    let tolerance = tolerance.max(0.0);
+    let cell_size = tolerance.max(1e-5);
+    let vertex_count = mesh.positions.len() / 3;
+    // attributes compared & kept for each vertex with their size, the missing ones are skipped
+    let mut attributes: Vec<(&mut Vec<f32>, usize)> = vec![
+        (&mut mesh.positions, 3),
+        (&mut mesh.normals, 3),
+        (&mut mesh.texcoords, 2),
+        (&mut mesh.vertex_color, 3),
+    ];
+    attributes.retain(|(values, size)| values.len() >= vertex_count * size);
+
+    let mut welded: Vec<Vec<f32>> = vec![Vec::new(); attributes.len()];
+    let mut cells: std::collections::HashMap<[i64; 3], Vec<u32>> = std::collections::HashMap::new();
+    let mut remap = Vec::with_capacity(vertex_count);
+    for vertex in 0..vertex_count {
+        let same = |kept: u32| {
+            attributes
+                .iter()
+                .zip(welded.iter())
+                .all(|((values, size), welded_values)| {
+                    let kept = kept as usize * size;
+                    (0..*size).all(|component| {
+                        (values[vertex * size + component] - welded_values[kept + component]).abs()
+                            <= tolerance
+                    })
+                })
+        };
+
+        let cell = weld_cell(&attributes[0].0[vertex * 3..vertex * 3 + 3], cell_size);
+        let mut found = None;
+        'search: for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let neighbour = [cell[0] + x, cell[1] + y, cell[2] + z];
+                    if let Some(kept) = cells
+                        .get(&neighbour)
+                        .and_then(|kept| kept.iter().copied().find(|kept| same(*kept)))
+                    {
+                        found = Some(kept);
+                        break 'search;
+                    }
+                }
+            }
+        }
+
+        let index = found.unwrap_or_else(|| {
+            let index = (welded[0].len() / 3) as u32;
+            for ((values, size), welded_values) in attributes.iter().zip(welded.iter_mut()) {
+                welded_values.extend_from_slice(&values[vertex * size..(vertex + 1) * size]);
+            }
+            cells.entry(cell).or_default().push(index);
+            index
+        });
+        remap.push(index);
+    }
+
+    for ((values, _), welded_values) in attributes.iter_mut().zip(welded) {
+        **values = welded_values;
+    }
+    // triangles with two corners merged have no area left
+    let mut indices = Vec::with_capacity(mesh.indices.len());
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|corner| remap[triangle[corner] as usize]);
+        if a != b && b != c && a != c {
+            indices.extend_from_slice(&[a, b, c]);
+        }
+    }
+    mesh.indices = indices;
+}
+
+// append `values` of `count` vertices after the `merged_count` ones of `merged`, the side
+// without the attribute is filled with `default` when the other one has it
+fn append_attribute(
+    merged: &mut Vec<f32>,
+    merged_count: usize,
+    values: &[f32],
+    count: usize,
+    size: usize,
+    default: f32,
+) {
+    let has_values = values.len() >= count * size;
+    if !has_values && merged.len() < merged_count * size {
+        return;
+    }
+    merged.resize(merged_count * size, default);
+    if has_values {
+        merged.extend_from_slice(&values[..count * size]);
+    } else {
+        merged.resize((merged_count + count) * size, default);
+    }
+}
+
+// append `mesh` drawn as `topology` to `merged`, baked in `transform`
+fn append_mesh(
+    merged: &mut Mesh,
+    mesh: Mesh,
+    topology: wgpu::PrimitiveTopology,
+    transform: cgmath::Matrix4<f32>,
+) {
+    let normal_matrix = cgmath::Matrix3::from_cols(
+        transform.x.truncate(),
+        transform.y.truncate(),
+        transform.z.truncate(),
+    )
+    .invert()
+    .unwrap_or_else(cgmath::Matrix3::identity)
+    .transpose();
+
+    let count = mesh.positions.len() / 3;
+    let merged_count = merged.positions.len() / 3;
+    let positions: Vec<[f32; 3]> = mesh
+        .positions
+        .chunks_exact(3)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect();
+    // the normals computed by `Model::load` when they are missing
+    let normals: Vec<[f32; 3]> = if mesh.normals.len() >= count * 3 {
+        mesh.normals
+            .chunks_exact(3)
+            .map(|n| [n[0], n[1], n[2]])
+            .collect()
+    } else if topology == wgpu::PrimitiveTopology::TriangleList {
+        crate::renderer::compute_normals(&positions, &mesh.indices)
+    } else {
+        vec![[0.0, 1.0, 0.0]; count]
+    };
+
+    for position in positions {
+        let position = transform.transform_point(cgmath::Point3::from(position));
+        merged
+            .positions
+            .extend_from_slice(&[position.x, position.y, position.z]);
+    }
+    for normal in normals.into_iter().take(count) {
+        let normal = (normal_matrix * cgmath::Vector3::from(normal)).normalize();
+        merged
+            .normals
+            .extend_from_slice(&[normal.x, normal.y, normal.z]);
+    }
+    append_attribute(
+        &mut merged.texcoords,
+        merged_count,
+        &mesh.texcoords,
+        count,
+        2,
+        0.0,
+    );
+    append_attribute(
+        &mut merged.vertex_color,
+        merged_count,
+        &mesh.vertex_color,
+        count,
+        3,
+        1.0,
+    );
+
+    // the strips of the merged meshes are kept apart
+    if topology.is_strip() && !merged.indices.is_empty() {
+        merged.indices.push(crate::renderer::PRIMITIVE_RESTART);
+    }
+    merged.indices.extend(mesh.indices.iter().map(|index| {
+        if *index == crate::renderer::PRIMITIVE_RESTART {
+            *index
+        } else {
+            index + merged_count as u32
+        }
+    }));
 }
 
 impl Object {
-    pub fn load_from<P: AsRef<Path>>(path: P) -> Object {
-        let (obj_models, obj_materials) = tobj::load_obj(
-            path.as_ref(),
-            &LoadOptions {
-                triangulate: true,
-                single_index: true,
-                ..Default::default()
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Object, ObjectError> {
+        Object::load_with_config(path, &LoadConfig::default())
+    }
+
+    pub fn load_with_config<P: AsRef<Path>>(
+        path: P,
+        load_config: &LoadConfig,
+    ) -> Result<Object, ObjectError> {
+        let (obj_models, obj_materials) =
+            tobj::load_obj(path.as_ref(), &Object::load_options()).map_err(ObjectError::Obj)?;
+
+        // We're assuming that the texture files are stored with the obj file
+        let containing_folder = path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+
+        Object::from_obj(
+            obj_models,
+            obj_materials.map_err(ObjectError::Material)?,
+            load_config,
+            |diffuse_path| {
+                image::open(containing_folder.join(diffuse_path))
+                    .map_err(|error| ObjectError::from_image(diffuse_path, error))
             },
         )
-        .unwrap();
+    }
 
-        let obj_materials = obj_materials.unwrap();
-        // We're assuming that the texture files are stored with the obj file
-        let containing_folder = path.as_ref().parent().unwrap();
+    // load from memory, the mtl files and the textures are given by name to `mtl_resolver`
+    pub fn load_from_reader<R: BufRead, F: Fn(&str) -> Option<Vec<u8>>>(
+        obj: R,
+        mtl_resolver: F,
+    ) -> Result<Object, ObjectError> {
+        Object::load_from_reader_with_config(obj, mtl_resolver, &LoadConfig::default())
+    }
+
+    pub fn load_from_reader_with_config<R: BufRead, F: Fn(&str) -> Option<Vec<u8>>>(
+        mut obj: R,
+        mtl_resolver: F,
+        load_config: &LoadConfig,
+    ) -> Result<Object, ObjectError> {
+        let (obj_models, obj_materials) =
+            tobj::load_obj_buf(&mut obj, &Object::load_options(), |mtl_path| {
+                let mtl =
+                    mtl_resolver(&mtl_path.to_string_lossy()).ok_or(LoadError::OpenFileFailed)?;
+                tobj::load_mtl_buf(&mut mtl.as_slice())
+            })
+            .map_err(ObjectError::Obj)?;
 
-        let mut textures: Vec<(image::DynamicImage, String, String)> = Vec::new();
+        Object::from_obj(
+            obj_models,
+            obj_materials.map_err(ObjectError::Material)?,
+            load_config,
+            |diffuse_path| {
+                let bytes = mtl_resolver(diffuse_path).ok_or_else(|| ObjectError::Io {
+                    path: diffuse_path.to_string(),
+                    source: std::io::ErrorKind::NotFound.into(),
+                })?;
+                image::load_from_memory(&bytes)
+                    .map_err(|error| ObjectError::from_image(diffuse_path, error))
+            },
+        )
+    }
+
+    // load a .gltf or .glb file, see gltf_import.rs for the supported features
+    pub fn load_gltf<P: AsRef<Path>>(path: P) -> Result<Object, ObjectError> {
+        crate::gltf_import::load(path.as_ref())
+    }
+
+    fn load_options() -> LoadOptions {
+        LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ..Default::default()
+        }
+    }
+
+    fn from_obj<F: Fn(&str) -> Result<image::DynamicImage, ObjectError>>(
+        mut obj_models: Vec<tobj::Model>,
+        obj_materials: Vec<tobj::Material>,
+        load_config: &LoadConfig,
+        load_image: F,
+    ) -> Result<Object, ObjectError> {
+        if load_config.flip_v {
+            for m in obj_models.iter_mut() {
+                for v in m.mesh.texcoords.iter_mut().skip(1).step_by(2) {
+                    *v = 1.0 - *v;
+                }
+            }
+        }
+        if !load_config.vertex_color_ao {
+            for m in obj_models.iter_mut() {
+                m.mesh.vertex_color.clear();
+            }
+        }
+        if let Some(tolerance) = load_config.weld_tolerance {
+            for m in obj_models.iter_mut() {
+                weld(&mut m.mesh, tolerance);
+            }
+        }
+
+        // obj files without mtl, e.g. scans, have no material bind group
+        let has_materials = !obj_materials.is_empty();
+        let mut textures: Vec<MaterialData> = Vec::new();
         for mat in obj_materials {
-            let diffuse_path = mat.diffuse_texture;
-            let path = containing_folder.join(diffuse_path.clone());
-            let img = image::open(path).unwrap();
+            // a single pixel of the diffuse color `Kd` without `map_Kd`
+            let (img, diffuse_label) = if mat.diffuse_texture.is_empty() {
+                let [r, g, b] = mat
+                    .diffuse
+                    .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+                (
+                    image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+                        1,
+                        1,
+                        image::Rgba([r, g, b, 255]),
+                    )),
+                    format!("{}_diffuse_color", mat.name),
+                )
+            } else {
+                (load_image(&mat.diffuse_texture)?, mat.diffuse_texture)
+            };
             let name = mat.name;
 
-            textures.push((img, diffuse_path, name));
+            textures.push(MaterialData {
+                name,
+                diffuse: img,
+                diffuse_label,
+                sampler: SamplerConfig {
+                    mip_level_count: if load_config.generate_mipmaps {
+                        SamplerConfig::FULL_MIP_CHAIN
+                    } else {
+                        1
+                    },
+                    address_mode: load_config.wrap,
+                    anisotropy_clamp: load_config.anisotropy_clamp,
+                    ..Default::default()
+                },
+                specular_color: mat.specular,
+                detail: None,
+                atlas: None,
+                normal: if mat.normal_texture.is_empty() {
+                    None
+                } else {
+                    Some(load_image(&mat.normal_texture)?)
+                },
+            });
         }
 
-        Object {
+        Ok(Object {
+            morph_targets: vec![Vec::new(); obj_models.len()],
+            topologies: vec![wgpu::PrimitiveTopology::TriangleList; obj_models.len()],
             models: obj_models,
-            textures: Some(textures),
+            textures: has_materials.then_some(textures),
+        })
+    }
+
+    // generate a grid of `scale` width displaced by the heightmap luminance
+    pub fn from_heightmap(
+        heightmap: &image::DynamicImage,
+        scale: f32,
+        height_scale: f32,
+    ) -> Object {
+        let heightmap = heightmap.to_luma8();
+        let (width, height) = (heightmap.width().max(2), heightmap.height().max(2));
+
+        let mut mesh = Mesh::default();
+        for z in 0..height {
+            for x in 0..width {
+                let u = x as f32 / (width - 1) as f32;
+                let v = z as f32 / (height - 1) as f32;
+                let luma = heightmap
+                    .get_pixel(x.min(heightmap.width() - 1), z.min(heightmap.height() - 1))
+                    .0[0];
+                mesh.positions.extend_from_slice(&[
+                    (u - 0.5) * scale,
+                    luma as f32 / 255.0 * height_scale,
+                    (v - 0.5) * scale,
+                ]);
+                mesh.texcoords.extend_from_slice(&[u, v]);
+            }
         }
+
+        // two counter-clockwise triangles for each cell, facing up
+        for z in 0..height - 1 {
+            for x in 0..width - 1 {
+                let top_left = z * width + x;
+                let bottom_left = top_left + width;
+                mesh.indices.extend_from_slice(&[
+                    top_left,
+                    bottom_left,
+                    top_left + 1,
+                    top_left + 1,
+                    bottom_left,
+                    bottom_left + 1,
+                ]);
+            }
+        }
+
+        let positions: Vec<[f32; 3]> = mesh
+            .positions
+            .chunks_exact(3)
+            .map(|p| [p[0], p[1], p[2]])
+            .collect();
+        mesh.normals = crate::renderer::compute_normals(&positions, &mesh.indices)
+            .into_iter()
+            .flatten()
+            .collect();
+
+        Object {
+            models: vec![Model::new(mesh, "heightmap".to_string())],
+            textures: None,
+            morph_targets: vec![Vec::new()],
+            topologies: vec![wgpu::PrimitiveTopology::TriangleList],
+        }
+    }
+
+    // flat grid of `width` x `depth` centered on the origin in the XZ plane, facing up,
+    // split in `subdivisions` cells along each side
+    pub fn plane(width: f32, depth: f32, subdivisions: u32) -> Object {
+        let cells = subdivisions.max(1);
+        let side = cells + 1;
+
+        let mut mesh = Mesh::default();
+        for z in 0..side {
+            for x in 0..side {
+                let u = x as f32 / cells as f32;
+                let v = z as f32 / cells as f32;
+                mesh.positions
+                    .extend_from_slice(&[(u - 0.5) * width, 0.0, (v - 0.5) * depth]);
+                mesh.normals.extend_from_slice(&[0.0, 1.0, 0.0]);
+                mesh.texcoords.extend_from_slice(&[u, v]);
+            }
+        }
+
+        // two counter-clockwise triangles for each cell, facing up
+        for z in 0..cells {
+            for x in 0..cells {
+                let top_left = z * side + x;
+                let bottom_left = top_left + side;
+                mesh.indices.extend_from_slice(&[
+                    top_left,
+                    bottom_left,
+                    top_left + 1,
+                    top_left + 1,
+                    bottom_left,
+                    bottom_left + 1,
+                ]);
+            }
+        }
+
+        Object {
+            models: vec![Model::new(mesh, "plane".to_string())],
+            textures: None,
+            morph_targets: vec![Vec::new()],
+            topologies: vec![wgpu::PrimitiveTopology::TriangleList],
+        }
+    }
+
+    // bake each object in its transform & join them in a single object, e.g. for static
+    // scenery. The meshes sharing a material & a topology are concatenated so that they are
+    // drawn in one call, the materials are appended in order. When some objects have
+    // materials, the meshes of the others get the plain white fallback one. Merged objects
+    // are static, their morph targets are dropped
+    pub fn merge(objects: Vec<(Object, cgmath::Matrix4<f32>)>) -> Object {
+        let has_materials = objects.iter().any(|(object, _)| object.textures.is_some());
+        let mut textures = Vec::new();
+        // material, topology & mesh of each merged model
+        let mut merged: Vec<(Option<usize>, wgpu::PrimitiveTopology, Mesh)> = Vec::new();
+
+        for (object, transform) in objects {
+            // the ids of this object come after the materials already merged, the ones
+            // missing from its materials get the fallback
+            let material_offset = textures.len();
+            let material_count = object.textures.as_ref().map_or(0, Vec::len);
+            for (model, topology) in object.models.into_iter().zip(object.topologies) {
+                let material_id = model
+                    .mesh
+                    .material_id
+                    .filter(|id| *id < material_count)
+                    .map(|id| id + material_offset);
+                let index = match merged.iter().position(|(id, merged_topology, _)| {
+                    *id == material_id && *merged_topology == topology
+                }) {
+                    Some(index) => index,
+                    None => {
+                        let mesh = Mesh {
+                            material_id,
+                            ..Default::default()
+                        };
+                        merged.push((material_id, topology, mesh));
+                        merged.len() - 1
+                    }
+                };
+                append_mesh(&mut merged[index].2, model.mesh, topology, transform);
+            }
+            if let Some(object_textures) = object.textures {
+                textures.extend(object_textures);
+            }
+        }
+
+        Object {
+            morph_targets: vec![Vec::new(); merged.len()],
+            topologies: merged.iter().map(|(_, topology, _)| *topology).collect(),
+            models: merged
+                .into_iter()
+                .enumerate()
+                .map(|(index, (_, _, mesh))| Model::new(mesh, format!("merged_{}", index)))
+                .collect(),
+            textures: has_materials.then_some(textures),
+        }
+    }
+
+    // use the same texture addressing for all the materials, e.g. Repeat for tiled textures
+    pub fn with_address_mode(mut self, address_mode: wgpu::AddressMode) -> Self {
+        if let Some(textures) = self.textures.as_mut() {
+            for material in textures {
+                material.sampler.address_mode = address_mode;
+            }
+        }
+        self
+    }
+
+    // number of mip levels of the diffuse textures & the LOD range they are sampled in
+    pub fn with_mip_levels(mut self, mip_level_count: u32, lod_min: f32, lod_max: f32) -> Self {
+        if let Some(textures) = self.textures.as_mut() {
+            for material in textures {
+                material.sampler.mip_level_count = mip_level_count;
+                material.sampler.lod_min_clamp = lod_min;
+                material.sampler.lod_max_clamp = lod_max;
+            }
+        }
+        self
+    }
+
+    // override the specular color of all the materials
+    pub fn with_specular_color(mut self, specular_color: [f32; 3]) -> Self {
+        if let Some(textures) = self.textures.as_mut() {
+            for material in textures {
+                material.specular_color = specular_color;
+            }
+        }
+        self
+    }
+
+    // blend a detail texture repeated `scale` times over the diffuse texture of all the materials
+    pub fn with_detail_texture(mut self, detail: image::DynamicImage, scale: f32) -> Self {
+        if let Some(textures) = self.textures.as_mut() {
+            for material in textures {
+                material.detail = Some((detail.clone(), scale));
+            }
+        }
+        self
+    }
+
+    // play a sprite sheet in place of the diffuse texture of all the materials
+    pub fn with_animated_atlas(
+        mut self,
+        texture: image::DynamicImage,
+        rows: u32,
+        columns: u32,
+        frames_per_second: f32,
+    ) -> Self {
+        if let Some(textures) = self.textures.as_mut() {
+            for material in textures {
+                material.diffuse = texture.clone();
+                material.atlas = Some((rows.max(1), columns.max(1), frames_per_second));
+            }
+        }
+        self
+    }
+
+    // draw the indices of a model as strips or lines instead of a triangle list, strips are
+    // restarted at each `PRIMITIVE_RESTART` index
+    pub fn set_topology(&mut self, model_index: usize, topology: wgpu::PrimitiveTopology) {
+        self.topologies[model_index] = topology;
+    }
+
+    // the target needs a delta for each vertex of the model, its normals can be left empty
+    pub fn add_morph_target(
+        &mut self,
+        model_index: usize,
+        morph_target: MorphTarget,
+    ) -> Result<(), ObjectError> {
+        let error = |reason| ObjectError::MorphTarget {
+            model_index,
+            reason,
+        };
+        let (model, targets) = match (
+            self.models.get(model_index),
+            self.morph_targets.get_mut(model_index),
+        ) {
+            (Some(model), Some(targets)) => (model, targets),
+            _ => return Err(error("there is no model at this index")),
+        };
+        let vertex_count = model.mesh.positions.len() / 3;
+        if morph_target.positions.len() != vertex_count {
+            return Err(error("the positions don't match the vertices of the model"));
+        }
+        if !morph_target.normals.is_empty() && morph_target.normals.len() != vertex_count {
+            return Err(error("the normals don't match the vertices of the model"));
+        }
+        if targets.len() >= crate::renderer::MAX_MORPH_TARGETS {
+            return Err(error("the model already has MAX_MORPH_TARGETS targets"));
+        }
+        targets.push(morph_target);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weld_merges_duplicates_within_tolerance() {
+        // the second triangle repeats two corners of the first 0.0005 away, the third starts
+        // 0.01 away from the first corner & repeats the others exactly
+        let mut mesh = Mesh {
+            positions: vec![
+                0.0, 0.0, 0.0, //
+                1.0, 0.0, 0.0, //
+                0.0, 1.0, 0.0, //
+                1.0005, 0.0, 0.0, //
+                1.0, 1.0, 0.0, //
+                0.0, 1.0005, 0.0, //
+                0.01, 0.0, 0.0, //
+                1.0, 0.0, 0.0, //
+                1.0, 1.0, 0.0, //
+            ],
+            indices: vec![0, 1, 2, 3, 4, 5, 6, 7, 8],
+            ..Default::default()
+        };
+        weld(&mut mesh, 0.001);
+
+        // the jittered corners are merged with the first ones, the far one is kept
+        assert_eq!(mesh.positions.len() / 3, 5);
+        assert_eq!(mesh.indices, [0, 1, 2, 1, 3, 2, 4, 1, 3]);
+        assert_eq!(&mesh.positions[12..15], [0.01, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn weld_drops_collapsed_triangles() {
+        let mut mesh = Mesh {
+            positions: vec![
+                0.0, 0.0, 0.0, //
+                0.0001, 0.0, 0.0, //
+                0.0, 1.0, 0.0, //
+            ],
+            indices: vec![0, 1, 2],
+            ..Default::default()
+        };
+        weld(&mut mesh, 0.001);
+
+        assert_eq!(mesh.positions.len() / 3, 2);
+        assert!(mesh.indices.is_empty());
     }
 }
@@ -0,0 +1,150 @@
+use crate::renderer::{Aabb, ModelRenderer};
+use crate::scene_manager::ObjectId;
+use cgmath::*;
+
+// Rays cast against the CPU copies of the geometries, e.g. to select the object under the
+// cursor. Meshes are tested as loaded, without their morph targets, and objects with custom
+// instances are skipped since their transforms only live in the shaders.
+
+// nearest intersection of a ray with the triangles of the objects
+#[derive(Debug, Copy, Clone)]
+pub struct Pick {
+    pub object: ObjectId,
+    // index of the mesh in the model of the object
+    pub mesh: usize,
+    // None when the object isn't instanced
+    pub instance: Option<usize>,
+    // index of the triangle in the order of `Geometry::triangles`
+    pub triangle: usize,
+    // distance along the ray, in units of its direction
+    pub t: f32,
+    // weights of the three vertices of the triangle at the hit
+    pub barycentric: [f32; 3],
+    pub position: Point3<f32>,
+}
+
+// hit of a single object
+pub(crate) struct Hit {
+    pub mesh: usize,
+    pub instance: Option<usize>,
+    pub triangle: usize,
+    pub t: f32,
+    pub barycentric: [f32; 3],
+}
+
+// Möller-Trumbore, both faces are hit
+fn ray_triangle(
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    [a, b, c]: [Point3<f32>; 3],
+) -> Option<(f32, [f32; 3])> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    // parallel to the triangle, or a degenerate one
+    if determinant.abs()
+        <= f32::EPSILON * edge1.magnitude() * edge2.magnitude() * direction.magnitude()
+    {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let s = origin - a;
+    let u = s.dot(p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let v = direction.dot(q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(q) * inverse;
+    (t > 0.0).then_some((t, [1.0 - u - v, u, v]))
+}
+
+// entry distance of the ray in the box, 0 when it starts inside
+fn ray_aabb(origin: Point3<f32>, direction: Vector3<f32>, aabb: &Aabb) -> Option<f32> {
+    let (mut near, mut far) = (0.0_f32, f32::MAX);
+    for axis in 0..3 {
+        if direction[axis] == 0.0 {
+            if origin[axis] < aabb.min[axis] || origin[axis] > aabb.max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let t1 = (aabb.min[axis] - origin[axis]) / direction[axis];
+        let t2 = (aabb.max[axis] - origin[axis]) / direction[axis];
+        near = near.max(t1.min(t2));
+        far = far.min(t1.max(t2));
+        if near > far {
+            return None;
+        }
+    }
+    Some(near)
+}
+
+// nearest hit of the model of a renderer, instances are tested in their own space where the
+// direction isn't normalized anymore, so that the distances stay the ones of the world ray
+pub(crate) fn intersect(
+    renderer: &ModelRenderer,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+) -> Option<Hit> {
+    if renderer.custom_instances.is_some() {
+        return None;
+    }
+    let transforms: Vec<(Option<usize>, Matrix4<f32>)> = if renderer.is_instanced() {
+        renderer
+            .instances
+            .iter()
+            .enumerate()
+            .map(|(index, instance)| (Some(index), Matrix4::from(instance.model)))
+            .collect()
+    } else {
+        vec![(None, renderer.transform())]
+    };
+
+    let mut nearest: Option<Hit> = None;
+    for (instance, transform) in transforms {
+        let inverse = match transform.invert() {
+            Some(inverse) => inverse,
+            None => continue,
+        };
+        let local_origin = inverse.transform_point(origin);
+        let local_direction = inverse.transform_vector(direction);
+
+        for (mesh_index, mesh) in renderer.model.meshes.iter().enumerate() {
+            let geometry = &mesh.geometry;
+            let max_t = nearest.as_ref().map_or(f32::MAX, |hit| hit.t);
+            match geometry
+                .bounds
+                .as_ref()
+                .and_then(|bounds| ray_aabb(local_origin, local_direction, bounds))
+            {
+                Some(entry) if entry < max_t => {}
+                _ => continue,
+            }
+            for (triangle, indices) in geometry.triangles().enumerate() {
+                let vertices = match indices.map(|index| geometry.vertices.get(index as usize)) {
+                    [Some(a), Some(b), Some(c)] => [a, b, c].map(|v| Point3::from(v.position)),
+                    _ => continue,
+                };
+                if let Some((t, barycentric)) =
+                    ray_triangle(local_origin, local_direction, vertices)
+                {
+                    if nearest.as_ref().map_or(true, |hit| t < hit.t) {
+                        nearest = Some(Hit {
+                            mesh: mesh_index,
+                            instance,
+                            triangle,
+                            t,
+                            barycentric,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    nearest
+}
@@ -1,4 +1,5 @@
-use crate::{camera, light, object, texture};
+use crate::{object, texture, water};
+use cgmath::prelude::*;
 use std::ops::Range;
 use wgpu::util::DeviceExt;
 
@@ -9,11 +10,18 @@ pub trait Vertex {
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ModelVertex {
-    position: [f32; 3],
-    tex_coords: [f32; 2],
-    normal: [f32; 3],
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub normal: [f32; 3],
+    // baked ambient occlusion, 1.0 when the model has none
+    pub ao: f32,
+    // tangent along the U texture coordinate, w is the handedness of the bitangent
+    pub tangent: [f32; 4],
 }
 
+// location of the tangent, after the instance attributes & the custom instance ones
+pub const TANGENT_LOCATION: u32 = 15;
+
 impl Vertex for ModelVertex {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         use std::mem;
@@ -36,20 +44,67 @@ impl Vertex for ModelVertex {
                     shader_location: 2,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: TANGENT_LOCATION,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
 }
 
+/// Instance attributes, the color at location 4 & the transform at locations 5 to 11. Objects
+/// added without instances are drawn as a single one, placed by their transform :
+///
+/// ```wgsl
+/// struct InstanceInput {
+///     [[location(4)]] color: vec4<f32>;
+///     [[location(5)]] model_matrix_0: vec4<f32>;
+///     [[location(6)]] model_matrix_1: vec4<f32>;
+///     [[location(7)]] model_matrix_2: vec4<f32>;
+///     [[location(8)]] model_matrix_3: vec4<f32>;
+/// };
+/// ```
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct InstanceRaw {
     pub model: [[f32; 4]; 4],
     pub normal: [[f32; 3]; 3],
+    // tint of the instance, multiplied with the base color by the built-in shaders
+    pub color: [f32; 4],
+    // keeps the size a multiple of 16 bytes
+    pub _padding: [f32; 3],
 }
 
 impl InstanceRaw {
-    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+    // white instance placed by `model`, the normals follow its inverse transpose
+    pub fn new(model: cgmath::Matrix4<f32>) -> Self {
+        let linear =
+            cgmath::Matrix3::from_cols(model.x.truncate(), model.y.truncate(), model.z.truncate());
+        let normal = linear
+            .invert()
+            .map(|inverse| inverse.transpose())
+            .unwrap_or(linear);
+        InstanceRaw {
+            model: model.into(),
+            normal: normal.into(),
+            color: [1.0; 4],
+            _padding: [0.0; 3],
+        }
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub(crate) fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         use std::mem;
         wgpu::VertexBufferLayout {
             array_stride: mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
@@ -98,11 +153,174 @@ impl InstanceRaw {
                     shader_location: 11,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                // location 4 is left free by the vertex attributes
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 25]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
 }
 
+// axis aligned bounding box
+#[derive(Debug, Copy, Clone)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    // None without positions
+    pub fn from_positions<I: IntoIterator<Item = [f32; 3]>>(positions: I) -> Option<Aabb> {
+        let mut positions = positions.into_iter();
+        let first = positions.next()?;
+        let mut aabb = Aabb {
+            min: first,
+            max: first,
+        };
+        positions.for_each(|position| aabb.extend(position));
+        Some(aabb)
+    }
+
+    // 0 inside the box
+    pub fn distance(&self, point: [f32; 3]) -> f32 {
+        (0..3)
+            .map(|axis| {
+                let outside = (self.min[axis] - point[axis]).max(point[axis] - self.max[axis]);
+                outside.max(0.0).powi(2)
+            })
+            .sum::<f32>()
+            .sqrt()
+    }
+
+    pub fn center(&self) -> [f32; 3] {
+        [
+            (self.min[0] + self.max[0]) / 2.0,
+            (self.min[1] + self.max[1]) / 2.0,
+            (self.min[2] + self.max[2]) / 2.0,
+        ]
+    }
+
+    // radius of the bounding sphere
+    pub fn radius(&self) -> f32 {
+        (cgmath::Vector3::from(self.max) - cgmath::Vector3::from(self.min)).magnitude() / 2.0
+    }
+
+    pub fn corners(&self) -> [[f32; 3]; 8] {
+        let mut corners = [[0.0; 3]; 8];
+        for (index, corner) in corners.iter_mut().enumerate() {
+            for (axis, value) in corner.iter_mut().enumerate() {
+                *value = if index & (1 << axis) == 0 {
+                    self.min[axis]
+                } else {
+                    self.max[axis]
+                };
+            }
+        }
+        corners
+    }
+
+    // bounds of the box once transformed, larger than the box when rotated
+    pub fn transformed(&self, matrix: cgmath::Matrix4<f32>) -> Aabb {
+        let mut corners = self
+            .corners()
+            .into_iter()
+            .map(|corner| matrix.transform_point(cgmath::Point3::from(corner)).into());
+        let first = corners.next().unwrap();
+        let mut aabb = Aabb {
+            min: first,
+            max: first,
+        };
+        corners.for_each(|corner| aabb.extend(corner));
+        aabb
+    }
+
+    fn extend(&mut self, position: [f32; 3]) {
+        for (axis, value) in position.iter().enumerate() {
+            self.min[axis] = self.min[axis].min(*value);
+            self.max[axis] = self.max[axis].max(*value);
+        }
+    }
+}
+
+// planes of the volume seen by a camera, a point is inside when a * x + b * y + c * z + d is
+// positive for all of them
+#[derive(Debug, Copy, Clone)]
+pub struct Frustum {
+    planes: [[f32; 4]; 6],
+}
+
+impl Frustum {
+    // planes of a wgpu view projection, the depth goes from 0 to 1
+    pub fn from_view_proj(view_proj: cgmath::Matrix4<f32>) -> Self {
+        // cgmath stores the columns
+        let row = |i: usize| {
+            cgmath::Vector4::new(
+                view_proj.x[i],
+                view_proj.y[i],
+                view_proj.z[i],
+                view_proj.w[i],
+            )
+        };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Frustum {
+            planes: [w + x, w - x, w + y, w - y, z, w - z].map(Into::into),
+        }
+    }
+
+    // false when the box is entirely on the outer side of a plane, boxes crossing the corners
+    // of the frustum may be kept while out of view
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // corner of the box the farthest inside the plane
+            let distance: f32 = (0..3)
+                .map(|axis| {
+                    plane[axis]
+                        * if plane[axis] >= 0.0 {
+                            aabb.max[axis]
+                        } else {
+                            aabb.min[axis]
+                        }
+                })
+                .sum();
+            distance + plane[3] >= 0.0
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ModelError {
+    // a material texture couldn't be uploaded
+    Texture {
+        material: String,
+        source: anyhow::Error,
+    },
+    // a model can't be drawn as a LOD of an object
+    Lod(&'static str),
+}
+
+impl std::fmt::Display for ModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelError::Texture { material, source } => {
+                write!(f, "texture of material {:?} : {}", material, source)
+            }
+            ModelError::Lod(reason) => write!(f, "LOD not added : {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ModelError::Texture { source, .. } => Some(source.as_ref()),
+            ModelError::Lod(_) => None,
+        }
+    }
+}
+
 pub struct Model {
     pub meshes: Vec<Mesh>,
     pub materials: Option<Vec<Material>>,
@@ -112,19 +330,335 @@ pub struct Model {
 pub struct Mesh {
     pub geometry: Geometry,
     pub material_id: Option<usize>,
+    pub morph_targets: Vec<object::MorphTarget>,
 }
 
+/// Material bind group, group 0 of the objects loaded with materials :
+///   binding 0 : diffuse texture
+///   binding 1 : diffuse sampler
+///   binding 2 : tangent space normal map, flat when the material has none
+///   binding 3 : normal sampler, configured like the diffuse one
+///   binding 4 : uniform with the material parameters
+///   binding 5 : detail texture, neutral gray when the material has none
+///   binding 6 : detail sampler, repeating
+///
+/// The tangents are passed at `TANGENT_LOCATION`, their w is the handedness of the
+/// bitangent. The detail texture is mid gray on average, the atlas offset & scale pick the
+/// current cell of a sprite sheet, 0 & 1 without atlas :
+///
+/// ```wgsl
+/// [[block]]
+/// struct MaterialParams {
+///     specular_color: vec3<f32>;
+///     detail_scale: f32;
+///     atlas_offset: vec2<f32>;
+///     atlas_scale: vec2<f32>;
+/// };
+/// [[group(0), binding(2)]]
+/// var t_normal: texture_2d<f32>;
+/// [[group(0), binding(3)]]
+/// var s_normal: sampler;
+/// [[group(0), binding(4)]]
+/// var<uniform> material: MaterialParams;
+/// [[group(0), binding(5)]]
+/// var t_detail: texture_2d<f32>;
+/// [[group(0), binding(6)]]
+/// var s_detail: sampler;
+///
+/// // normal & tangent in world space, interpolated from the vertex shader
+/// fn perturb_normal(normal: vec3<f32>, tangent: vec4<f32>, tex_coords: vec2<f32>) -> vec3<f32> {
+///     let bitangent = cross(normal, tangent.xyz) * tangent.w;
+///     let tangent_normal = textureSample(t_normal, s_normal, tex_coords).xyz * 2.0 - 1.0;
+///     return normalize(mat3x3<f32>(tangent.xyz, bitangent, normal) * tangent_normal);
+/// }
+///
+/// // in fs_main, the detail fades out after 10 units
+/// let tex_coords = material.atlas_offset + in.tex_coords * material.atlas_scale;
+/// let detail = textureSample(t_detail, s_detail, in.tex_coords * material.detail_scale).rgb * 2.0;
+/// let fade = clamp(distance(in.world_position, camera.view_pos.xyz) / 10.0, 0.0, 1.0);
+/// let color = object_color.rgb * mix(detail, vec3<f32>(1.0), fade);
+/// ```
 pub struct Material {
     pub name: String,
     pub diffuse_texture: texture::Texture,
+    // CPU copy of the diffuse texture, the base color of the glTF exports
+    pub diffuse_image: image::DynamicImage,
+    pub detail_texture: texture::Texture,
+    pub normal_texture: texture::Texture,
+    pub params_buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
+    // CPU copy of the parameters, rewritten when the atlas cell changes
+    params: MaterialUniform,
+    // rows, columns & frames per second of the sprite sheet
+    pub atlas: Option<(u32, u32, f32)>,
 }
 
+impl Material {
+    // move the sampled cell of the atlas to the frame shown at `elapsed`
+    pub fn update_atlas(&mut self, queue: &wgpu::Queue, elapsed: std::time::Duration) {
+        let (rows, columns, frames_per_second) = match self.atlas {
+            Some(atlas) => atlas,
+            None => return,
+        };
+        let frame =
+            (elapsed.as_secs_f32() * frames_per_second).max(0.0) as u64 % (rows * columns) as u64;
+        let (row, column) = (frame as u32 / columns, frame as u32 % columns);
+        let offset = [column as f32 / columns as f32, row as f32 / rows as f32];
+        if offset != self.params.atlas_offset {
+            self.params.atlas_offset = offset;
+            queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[self.params]));
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    specular_color: [f32; 3],
+    // tiling of the detail texture over the diffuse one, 0 without detail texture
+    detail_scale: f32,
+    // uv = atlas_offset + tex_coords * atlas_scale samples the current cell of a sprite sheet,
+    // 0 & 1 without atlas
+    atlas_offset: [f32; 2],
+    atlas_scale: [f32; 2],
+}
+
+// detail textures are multiplied by twice their value, so mid gray leaves the diffuse unchanged
+const NEUTRAL_DETAIL: [u8; 4] = [128, 128, 128, 255];
+// tangent space normal pointing straight out of the surface
+const FLAT_NORMAL: [u8; 4] = [128, 128, 255, 255];
+
 pub struct Geometry {
     pub name: String,
+    // keep a CPU copy of the geometry to be able to process it after loading
+    pub vertices: Vec<ModelVertex>,
+    pub indices: Vec<u32>,
     pub vertex_buffer: wgpu::Buffer,
+    // COPY_DST is added to the vertex buffer once the geometry is marked dynamic
+    vertex_usage: wgpu::BufferUsages,
     pub index_buffer: wgpu::Buffer,
     pub num_elements: u32,
+    // strips are restarted at each PRIMITIVE_RESTART index
+    pub topology: wgpu::PrimitiveTopology,
+    // bounds of the vertices when loaded, None without vertices
+    pub bounds: Option<Aabb>,
+}
+
+// index ending a strip, the next index starts a new one
+pub const PRIMITIVE_RESTART: u32 = u32::MAX;
+
+impl Geometry {
+    pub fn new(
+        device: &wgpu::Device,
+        name: String,
+        vertices: Vec<ModelVertex>,
+        indices: Vec<u32>,
+    ) -> Self {
+        let vertex_usage = wgpu::BufferUsages::VERTEX;
+        let vertex_buffer = Geometry::create_vertex_buffer(device, &name, &vertices, vertex_usage);
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Index Buffer", name)),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Geometry {
+            name,
+            num_elements: indices.len() as u32,
+            bounds: Aabb::from_positions(vertices.iter().map(|vertex| vertex.position)),
+            vertices,
+            indices,
+            vertex_buffer,
+            vertex_usage,
+            index_buffer,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+        }
+    }
+
+    pub fn with_topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    // triangles drawn by the indices, 0 for lines & points
+    pub fn triangle_count(&self) -> u64 {
+        match self.topology {
+            wgpu::PrimitiveTopology::TriangleList => self.indices.len() as u64 / 3,
+            wgpu::PrimitiveTopology::TriangleStrip => self
+                .indices
+                .split(|index| *index == PRIMITIVE_RESTART)
+                .map(|strip| strip.len().saturating_sub(2) as u64)
+                .sum(),
+            _ => 0,
+        }
+    }
+
+    // vertex indices of the triangles drawn by the indices, the strips are unrolled,
+    // none for lines & points
+    pub fn triangles(&self) -> Box<dyn Iterator<Item = [u32; 3]> + '_> {
+        match self.topology {
+            wgpu::PrimitiveTopology::TriangleList => Box::new(
+                self.indices
+                    .chunks_exact(3)
+                    .map(|triangle| [triangle[0], triangle[1], triangle[2]]),
+            ),
+            wgpu::PrimitiveTopology::TriangleStrip => Box::new(
+                self.indices
+                    .split(|index| *index == PRIMITIVE_RESTART)
+                    .flat_map(|strip| strip.windows(3))
+                    .map(|triangle| [triangle[0], triangle[1], triangle[2]]),
+            ),
+            _ => Box::new(std::iter::empty()),
+        }
+    }
+
+    fn create_vertex_buffer(
+        device: &wgpu::Device,
+        name: &str,
+        vertices: &[ModelVertex],
+        usage: wgpu::BufferUsages,
+    ) -> wgpu::Buffer {
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Vertex Buffer", name)),
+            contents: bytemuck::cast_slice(vertices),
+            usage,
+        })
+    }
+
+    // every triangle gets its own vertices, the index buffer is 0..n
+    pub fn unindexed(&self, device: &wgpu::Device) -> Geometry {
+        let vertices = self
+            .indices
+            .iter()
+            .map(|index| self.vertices[*index as usize])
+            .collect::<Vec<_>>();
+        let indices = (0..vertices.len() as u32).collect();
+        Geometry::new(device, self.name.clone(), vertices, indices)
+    }
+
+    // allow in-place updates of the vertex buffer with `queue.write_buffer`
+    pub fn set_dynamic(&mut self, device: &wgpu::Device) {
+        if self.vertex_usage.contains(wgpu::BufferUsages::COPY_DST) {
+            return;
+        }
+        self.vertex_usage |= wgpu::BufferUsages::COPY_DST;
+        self.vertex_buffer =
+            Geometry::create_vertex_buffer(device, &self.name, &self.vertices, self.vertex_usage);
+    }
+
+    // the faces are only known for triangle lists, other topologies are left untouched
+    pub fn recompute_normals(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.topology != wgpu::PrimitiveTopology::TriangleList {
+            return;
+        }
+        let positions: Vec<[f32; 3]> = self.vertices.iter().map(|v| v.position).collect();
+        let normals = compute_normals(&positions, &self.indices);
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            vertex.normal = normal;
+        }
+        // the tangents are kept perpendicular to the new normals
+        let tangents = compute_tangents(&self.vertices, &self.indices);
+        for (vertex, tangent) in self.vertices.iter_mut().zip(tangents) {
+            vertex.tangent = tangent;
+        }
+
+        // the first call recreates the buffer writable with the new normals, the next ones
+        // write in place
+        if self.vertex_usage.contains(wgpu::BufferUsages::COPY_DST) {
+            queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        } else {
+            self.set_dynamic(device);
+        }
+    }
+}
+
+// compute smooth vertex normals by averaging the normals of the adjacent faces
+pub fn compute_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![cgmath::Vector3::<f32>::zero(); positions.len()];
+    for face in indices.chunks_exact(3) {
+        let (a, b, c) = (face[0] as usize, face[1] as usize, face[2] as usize);
+        let p0 = cgmath::Vector3::from(positions[a]);
+        let p1 = cgmath::Vector3::from(positions[b]);
+        let p2 = cgmath::Vector3::from(positions[c]);
+        // the cross product is not normalized so bigger faces weigh more
+        let face_normal = (p1 - p0).cross(p2 - p0);
+        normals[a] += face_normal;
+        normals[b] += face_normal;
+        normals[c] += face_normal;
+    }
+
+    normals
+        .into_iter()
+        .map(|normal| {
+            if normal.magnitude2() > 0.0 {
+                normal.normalize().into()
+            } else {
+                [0.0, 1.0, 0.0]
+            }
+        })
+        .collect()
+}
+
+// any unit vector perpendicular to the normal, for the vertices without texture coordinates
+fn perpendicular(normal: cgmath::Vector3<f32>) -> cgmath::Vector3<f32> {
+    let axis = if normal.x.abs() < 0.9 {
+        cgmath::Vector3::unit_x()
+    } else {
+        cgmath::Vector3::unit_y()
+    };
+    axis.cross(normal).normalize()
+}
+
+// per-vertex tangents from the texture coordinates of the adjacent faces of a triangle list,
+// made perpendicular to the normals. The handedness in w flips the bitangent of mirrored UVs
+pub fn compute_tangents(vertices: &[ModelVertex], indices: &[u32]) -> Vec<[f32; 4]> {
+    let mut tangents = vec![cgmath::Vector3::<f32>::zero(); vertices.len()];
+    let mut bitangents = vec![cgmath::Vector3::<f32>::zero(); vertices.len()];
+    for face in indices.chunks_exact(3) {
+        let (a, b, c) = (face[0] as usize, face[1] as usize, face[2] as usize);
+        if a.max(b).max(c) >= vertices.len() {
+            continue;
+        }
+        let p0 = cgmath::Vector3::from(vertices[a].position);
+        let e1 = cgmath::Vector3::from(vertices[b].position) - p0;
+        let e2 = cgmath::Vector3::from(vertices[c].position) - p0;
+        let uv0 = cgmath::Vector2::from(vertices[a].tex_coords);
+        let duv1 = cgmath::Vector2::from(vertices[b].tex_coords) - uv0;
+        let duv2 = cgmath::Vector2::from(vertices[c].tex_coords) - uv0;
+        let determinant = duv1.x * duv2.y - duv2.x * duv1.y;
+        // faces without UV area don't orient the tangents
+        if determinant.abs() <= f32::EPSILON {
+            continue;
+        }
+        // not normalized so bigger faces weigh more, like the normals
+        let tangent = (e1 * duv2.y - e2 * duv1.y) / determinant;
+        let bitangent = (e2 * duv1.x - e1 * duv2.x) / determinant;
+        for vertex in [a, b, c] {
+            tangents[vertex] += tangent;
+            bitangents[vertex] += bitangent;
+        }
+    }
+
+    vertices
+        .iter()
+        .zip(tangents.into_iter().zip(bitangents))
+        .map(|(vertex, (tangent, bitangent))| {
+            let normal = cgmath::Vector3::from(vertex.normal);
+            // Gram-Schmidt, the tangent is projected on the plane of the normal
+            let tangent = tangent - normal * normal.dot(tangent);
+            let tangent = if tangent.magnitude2() > f32::EPSILON {
+                tangent.normalize()
+            } else {
+                perpendicular(normal)
+            };
+            let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            tangent.extend(handedness).into()
+        })
+        .collect()
 }
 
 impl Model {
@@ -132,12 +666,32 @@ impl Model {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         object: object::Object,
-    ) -> Result<Self, ()> {
-        let (obj_models, textures) = (object.models, object.textures);
+        // materials with the same sampler config share their sampler
+        sampler_pool: &mut texture::SamplerPool,
+    ) -> Result<Self, ModelError> {
+        let (obj_models, textures, morph_targets, topologies) = (
+            object.models,
+            object.textures,
+            object.morph_targets,
+            object.topologies,
+        );
+
+        // meshes without a material, or with one missing from the mtl file, get a plain white
+        // one appended after the others so that every mesh binds the material group
+        let material_count = textures.as_ref().map_or(0, Vec::len);
+        let textures = textures.map(|mut textures| {
+            if obj_models
+                .iter()
+                .any(|m| m.mesh.material_id.map_or(true, |id| id >= material_count))
+            {
+                textures.push(object::MaterialData::fallback());
+            }
+            textures
+        });
 
         let mut material_flag = false;
 
-        let material_layout = if let Some(_) = &textures {
+        let material_layout = if textures.is_some() {
             material_flag = true;
 
             let material_layout =
@@ -166,6 +720,54 @@ impl Model {
                             },
                             count: None,
                         },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                multisampled: false,
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler {
+                                comparison: false,
+                                filtering: true,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 4,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 5,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                multisampled: false,
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 6,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler {
+                                comparison: false,
+                                filtering: true,
+                            },
+                            count: None,
+                        },
                     ],
                     label: Some("material_bind_group_layout"),
                 });
@@ -175,138 +777,710 @@ impl Model {
             None
         };
 
-        let materials = if let Some(material_textures) = textures {
-            let mut materials = Vec::new();
-            for texture in material_textures.iter() {
-                let (diffuse_img, diffuse_label, name) = texture;
-                let diffuse_texture = texture::Texture::from_image(
-                    device,
-                    queue,
-                    diffuse_img,
-                    Some(diffuse_label.as_str()),
-                )
-                .unwrap();
+        let materials =
+            if let Some(material_textures) = textures {
+                let mut materials = Vec::new();
+                for material in material_textures {
+                    let diffuse_texture = texture::Texture::from_image_with_sampler_pool(
+                        device,
+                        queue,
+                        &material.diffuse,
+                        Some(material.diffuse_label.as_str()),
+                        &material.sampler,
+                        sampler_pool,
+                    )
+                    .map_err(|source| ModelError::Texture {
+                        material: material.name.clone(),
+                        source,
+                    })?;
 
-                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    layout: &material_layout.as_ref().unwrap(),
-                    entries: &[
-                        wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
+                    let neutral_detail = image::DynamicImage::ImageRgba8(
+                        image::RgbaImage::from_pixel(1, 1, image::Rgba(NEUTRAL_DETAIL)),
+                    );
+                    let (detail, detail_scale) = match material.detail.as_ref() {
+                        Some((detail, scale)) => (detail, *scale),
+                        None => (&neutral_detail, 0.0),
+                    };
+                    let detail_texture = texture::Texture::from_image_with_sampler_pool(
+                        device,
+                        queue,
+                        detail,
+                        Some("detail_texture"),
+                        &texture::SamplerConfig {
+                            address_mode: wgpu::AddressMode::Repeat,
+                            ..Default::default()
                         },
-                        wgpu::BindGroupEntry {
-                            binding: 1,
-                            resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
+                        sampler_pool,
+                    )
+                    .map_err(|source| ModelError::Texture {
+                        material: material.name.clone(),
+                        source,
+                    })?;
+
+                    let flat_normal = image::DynamicImage::ImageRgba8(
+                        image::RgbaImage::from_pixel(1, 1, image::Rgba(FLAT_NORMAL)),
+                    );
+                    // sampled like the diffuse texture it is drawn over
+                    let normal_texture = texture::Texture::from_linear_image_with_sampler_pool(
+                        device,
+                        queue,
+                        material.normal.as_ref().unwrap_or(&flat_normal),
+                        Some("normal_texture"),
+                        &material.sampler,
+                        sampler_pool,
+                    )
+                    .map_err(|source| ModelError::Texture {
+                        material: material.name.clone(),
+                        source,
+                    })?;
+
+                    let material_uniform = MaterialUniform {
+                        specular_color: material.specular_color,
+                        detail_scale,
+                        atlas_offset: [0.0; 2],
+                        atlas_scale: match material.atlas {
+                            Some((rows, columns, _)) => [1.0 / columns as f32, 1.0 / rows as f32],
+                            None => [1.0; 2],
                         },
-                    ],
-                    label: None,
-                });
+                    };
+                    let params_buffer =
+                        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                            label: Some("Material Params Buffer"),
+                            contents: bytemuck::cast_slice(&[material_uniform]),
+                            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                        });
 
-                let material_name = name.clone();
+                    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        layout: material_layout.as_ref().unwrap(),
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 2,
+                                resource: wgpu::BindingResource::TextureView(&normal_texture.view),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 3,
+                                resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 4,
+                                resource: params_buffer.as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 5,
+                                resource: wgpu::BindingResource::TextureView(&detail_texture.view),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 6,
+                                resource: wgpu::BindingResource::Sampler(&detail_texture.sampler),
+                            },
+                        ],
+                        label: None,
+                    });
 
-                materials.push(Material {
-                    name: material_name,
-                    diffuse_texture,
-                    bind_group,
-                });
-            }
+                    let material_name = material.name.clone();
 
-            Some(materials)
-        } else {
-            None
-        };
+                    materials.push(Material {
+                        name: material_name,
+                        diffuse_texture,
+                        diffuse_image: material.diffuse,
+                        detail_texture,
+                        normal_texture,
+                        params_buffer,
+                        bind_group,
+                        params: material_uniform,
+                        atlas: material.atlas,
+                    });
+                }
+
+                Some(materials)
+            } else {
+                None
+            };
 
         let mut meshes = Vec::new();
-        for m in obj_models {
+        for ((m, morph_targets), topology) in
+            obj_models.into_iter().zip(morph_targets).zip(topologies)
+        {
+            let vertex_count = m.mesh.positions.len() / 3;
+            let has_tex_coords = m.mesh.texcoords.len() >= vertex_count * 2;
+            // obj files exported without normals are shaded with the averaged face normals
+            let computed_normals = (m.mesh.normals.len() < vertex_count * 3).then(|| {
+                if topology == wgpu::PrimitiveTopology::TriangleList {
+                    let positions: Vec<[f32; 3]> = m
+                        .mesh
+                        .positions
+                        .chunks_exact(3)
+                        .map(|p| [p[0], p[1], p[2]])
+                        .collect();
+                    compute_normals(&positions, &m.mesh.indices)
+                } else {
+                    vec![[0.0, 1.0, 0.0]; vertex_count]
+                }
+            });
+
             let mut vertices = Vec::new();
-            for i in 0..m.mesh.positions.len() / 3 {
+            for i in 0..vertex_count {
                 vertices.push(ModelVertex {
                     position: [
                         m.mesh.positions[i * 3],
                         m.mesh.positions[i * 3 + 1],
                         m.mesh.positions[i * 3 + 2],
                     ],
-                    tex_coords: [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]],
-                    normal: [
-                        m.mesh.normals[i * 3],
-                        m.mesh.normals[i * 3 + 1],
-                        m.mesh.normals[i * 3 + 2],
-                    ],
+                    tex_coords: if has_tex_coords {
+                        [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]]
+                    } else {
+                        [0.0, 0.0]
+                    },
+                    normal: match computed_normals.as_ref() {
+                        Some(normals) => normals[i],
+                        None => [
+                            m.mesh.normals[i * 3],
+                            m.mesh.normals[i * 3 + 1],
+                            m.mesh.normals[i * 3 + 2],
+                        ],
+                    },
+                    // the AO is baked in the vertex colors as a gray level, see
+                    // `LoadConfig::vertex_color_ao`
+                    ao: m
+                        .mesh
+                        .vertex_color
+                        .get(i * 3..i * 3 + 3)
+                        .map_or(1.0, |color| color.iter().sum::<f32>() / 3.0),
+                    tangent: [0.0; 4],
                 });
             }
+            // strips have no faces to orient the tangents, they are only perpendicular
+            let faces: &[u32] =
+                if has_tex_coords && topology == wgpu::PrimitiveTopology::TriangleList {
+                    &m.mesh.indices
+                } else {
+                    &[]
+                };
+            let tangents = compute_tangents(&vertices, faces);
+            for (vertex, tangent) in vertices.iter_mut().zip(tangents) {
+                vertex.tangent = tangent;
+            }
 
-            let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{:?} Vertex Buffer", &m.name)),
-                contents: bytemuck::cast_slice(&vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
-            let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{:?} Index Buffer", &m.name)),
-                contents: bytemuck::cast_slice(&m.mesh.indices),
-                usage: wgpu::BufferUsages::INDEX,
-            });
-
-            let geometry = Geometry {
-                name: m.name,
-                vertex_buffer,
-                index_buffer,
-                num_elements: m.mesh.indices.len() as u32,
-            };
+            let geometry =
+                Geometry::new(device, m.name, vertices, m.mesh.indices).with_topology(topology);
 
             if material_flag {
                 meshes.push(Mesh {
-                    geometry: geometry,
-                    material_id: Some(m.mesh.material_id.unwrap_or(0)),
+                    geometry,
+                    material_id: Some(
+                        m.mesh
+                            .material_id
+                            .filter(|id| *id < material_count)
+                            .unwrap_or(material_count),
+                    ),
+                    morph_targets,
                 });
             } else {
                 meshes.push(Mesh {
-                    geometry: geometry,
+                    geometry,
                     material_id: None,
+                    morph_targets,
                 });
             }
         }
 
         Ok(Self {
-            meshes: meshes,
-            materials: materials,
-            material_layout: material_layout,
+            meshes,
+            materials,
+            material_layout,
         })
     }
+
+    // bounds of all the meshes, None when the model has no vertex
+    pub fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_positions(
+            self.meshes
+                .iter()
+                .filter_map(|mesh| mesh.geometry.bounds)
+                .flat_map(|bounds| [bounds.min, bounds.max]),
+        )
+    }
+
+    pub fn recompute_normals(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        for mesh in &mut self.meshes {
+            mesh.geometry.recompute_normals(device, queue);
+        }
+    }
+}
+
+// user defined per-instance data, bound after the `InstanceRaw` buffer when there is one,
+// the attributes should use shader locations from 12 to 14 to not conflict with it & with the
+// tangent of the vertices
+pub struct CustomInstances {
+    pub data: Vec<u8>,
+    pub array_stride: wgpu::BufferAddress,
+    pub attributes: Vec<wgpu::VertexAttribute>,
+    pub length: usize,
+}
+
+impl CustomInstances {
+    pub fn new<T: bytemuck::Pod>(instances: &[T], attributes: Vec<wgpu::VertexAttribute>) -> Self {
+        CustomInstances {
+            data: bytemuck::cast_slice(instances).to_vec(),
+            array_stride: std::mem::size_of::<T>() as wgpu::BufferAddress,
+            attributes,
+            length: instances.len(),
+        }
+    }
+
+    fn desc(&self) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: self.array_stride,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &self.attributes,
+        }
+    }
+}
+
+// pipeline state chosen for each object
+#[derive(Debug, Copy, Clone)]
+pub struct RenderOptions {
+    pub front_face: wgpu::FrontFace,
+    // Line & Point need an adapter feature, the object is filled without it
+    pub polygon_mode: wgpu::PolygonMode,
+    // drawn after the opaque objects with the scene color bound
+    pub samples_scene_color: bool,
+    // min & max depth of the viewport used for this object
+    pub viewport_depth: Option<(f32, f32)>,
+    pub blend: wgpu::BlendState,
+    pub depth_write_enabled: bool,
+    pub depth_compare: wgpu::CompareFunction,
+    pub depth_bias: wgpu::DepthBiasState,
+    // buffers updated from the CPU get COPY_DST, static ones are left without it
+    pub dynamic_instances: bool,
+    pub dynamic_vertices: bool,
+    // requires the scene to have a stencil buffer, which is created when any object uses it
+    pub stencil: Option<StencilMode>,
+    // smooth the edges of cutout materials from their alpha, only effective with multisampling
+    pub alpha_to_coverage: bool,
+    // draw the edges of the triangles with the built-in barycentric wireframe shader
+    pub wireframe: bool,
+    // draw the base color with the built-in unlit shader, the light bind group is left out
+    pub unlit: bool,
+    // drawn before the other opaque objects without writing the depth, so that they always
+    // cover it, e.g. a sun disc or distant mountains
+    pub background: bool,
+    // blended over the opaque objects, drawn after them without writing the depth
+    pub transparent: bool,
+    // draw the object with the built-in water shader, which doesn't read the light
+    pub water: Option<water::WaterParams>,
+    // skip the meshes whose bounds are out of the camera frustum, to turn off for shaders
+    // moving the vertices beyond the loaded positions
+    pub culling: bool,
+    // drawn in the shadow map of the first light when the scene has shadows
+    pub casts_shadows: bool,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum StencilMode {
+    // write the reference value where the object is drawn
+    Mask(u32),
+    // only draw where the stencil equals the reference value
+    Test(u32),
+}
+
+impl StencilMode {
+    pub fn reference(&self) -> u32 {
+        match self {
+            StencilMode::Mask(reference) | StencilMode::Test(reference) => *reference,
+        }
+    }
+
+    fn state(&self) -> wgpu::StencilState {
+        let (face, write_mask) = match self {
+            StencilMode::Mask(_) => (
+                wgpu::StencilFaceState {
+                    compare: wgpu::CompareFunction::Always,
+                    fail_op: wgpu::StencilOperation::Keep,
+                    depth_fail_op: wgpu::StencilOperation::Keep,
+                    pass_op: wgpu::StencilOperation::Replace,
+                },
+                0xff,
+            ),
+            StencilMode::Test(_) => (
+                wgpu::StencilFaceState {
+                    compare: wgpu::CompareFunction::Equal,
+                    fail_op: wgpu::StencilOperation::Keep,
+                    depth_fail_op: wgpu::StencilOperation::Keep,
+                    pass_op: wgpu::StencilOperation::Keep,
+                },
+                0x00,
+            ),
+        };
+        wgpu::StencilState {
+            front: face,
+            back: face,
+            read_mask: 0xff,
+            write_mask,
+        }
+    }
+}
+
+// formats of the attachments a pipeline renders to
+#[derive(Debug, Copy, Clone)]
+pub struct TargetFormats {
+    pub color: wgpu::TextureFormat,
+    pub depth: wgpu::TextureFormat,
+    // samples per pixel of both attachments, 1 without MSAA
+    pub sample_count: u32,
+}
+
+impl RenderOptions {
+    // feature the device needs to rasterize with `polygon_mode`
+    pub(crate) fn polygon_mode_feature(polygon_mode: wgpu::PolygonMode) -> wgpu::Features {
+        match polygon_mode {
+            wgpu::PolygonMode::Fill => wgpu::Features::empty(),
+            wgpu::PolygonMode::Line => wgpu::Features::POLYGON_MODE_LINE,
+            wgpu::PolygonMode::Point => wgpu::Features::POLYGON_MODE_POINT,
+        }
+    }
+
+    // pull the object toward the camera so it sits on top of the surface behind it
+    pub const DECAL_DEPTH_BIAS: wgpu::DepthBiasState = wgpu::DepthBiasState {
+        constant: -4,
+        slope_scale: -2.0,
+        clamp: 0.0,
+    };
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            front_face: wgpu::FrontFace::Ccw,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            samples_scene_color: false,
+            viewport_depth: None,
+            blend: wgpu::BlendState::REPLACE,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            depth_bias: wgpu::DepthBiasState::default(),
+            dynamic_instances: false,
+            dynamic_vertices: false,
+            stencil: None,
+            alpha_to_coverage: false,
+            wireframe: false,
+            unlit: false,
+            background: false,
+            transparent: false,
+            water: None,
+            culling: true,
+            casts_shadows: true,
+        }
+    }
+}
+
+pub const MAX_MORPH_TARGETS: usize = 8;
+
+/// WGSL declaring the morph bind group & blending its targets, to prepend to a vertex shader
+/// once MORPH_GROUP is replaced by the group index, see the top of the file :
+///
+/// ```wgsl
+/// [[stage(vertex)]]
+/// fn vs_main([[builtin(vertex_index)]] vertex_index: u32, model: VertexInput) -> VertexOutput {
+///     let position = morph_position(model.position, vertex_index);
+///     let normal = morph_normal(model.normal, vertex_index);
+///     ...
+/// }
+/// ```
+pub const MORPH_WGSL: &str = include_str!("shader/morph.wgsl");
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MorphUniform {
+    weights: [[f32; 4]; MAX_MORPH_TARGETS / 4],
+    count: u32,
+    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
+    _padding: [u32; 3],
+}
+
+// Morph targets are bound after the camera & light bind groups :
+//   binding 0 : uniform with the weights and the number of targets
+//   binding 1 : read-only storage of vec4 deltas, for each vertex and each target
+//               the position delta then the normal delta,
+//               at index (vertex_index * count + target) * 2
+pub struct MorphBinder {
+    morph_uniform: MorphUniform,
+    weights_buffer: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    // one bind group for each mesh of the model
+    pub bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl MorphBinder {
+    fn bind(device: &wgpu::Device, meshes: &[Mesh]) -> Option<Self> {
+        let count = meshes
+            .iter()
+            .map(|mesh| mesh.morph_targets.len())
+            .max()
+            .unwrap_or(0)
+            .min(MAX_MORPH_TARGETS);
+        if count == 0 {
+            return None;
+        }
+
+        let morph_uniform = MorphUniform {
+            weights: [[0.0; 4]; MAX_MORPH_TARGETS / 4],
+            count: count as u32,
+            _padding: [0; 3],
+        };
+        let weights_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Morph Weights Buffer"),
+            contents: bytemuck::cast_slice(&[morph_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("morph_bind_group_layout"),
+        });
+
+        let bind_groups = meshes
+            .iter()
+            .map(|mesh| {
+                // meshes with less targets than the model get zero deltas
+                let mut deltas = vec![[0.0f32; 4]; mesh.geometry.vertices.len() * count * 2];
+                for (target_index, target) in mesh.morph_targets.iter().take(count).enumerate() {
+                    for (vertex_index, position) in target.positions.iter().enumerate() {
+                        let index = (vertex_index * count + target_index) * 2;
+                        deltas[index] = [position[0], position[1], position[2], 0.0];
+                    }
+                    for (vertex_index, normal) in target.normals.iter().enumerate() {
+                        let index = (vertex_index * count + target_index) * 2 + 1;
+                        deltas[index] = [normal[0], normal[1], normal[2], 0.0];
+                    }
+                }
+                let deltas_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{:?} Morph Deltas Buffer", mesh.geometry.name)),
+                    contents: bytemuck::cast_slice(&deltas),
+                    usage: wgpu::BufferUsages::STORAGE,
+                });
+
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: weights_buffer.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: deltas_buffer.as_entire_binding(),
+                        },
+                    ],
+                    label: Some("morph_bind_group"),
+                })
+            })
+            .collect();
+
+        Some(MorphBinder {
+            morph_uniform,
+            weights_buffer,
+            bind_group_layout,
+            bind_groups,
+        })
+    }
+}
+
+// Extra textures of an object, bound after the morph targets (or the common bind groups
+// when the model has none), for each texture in the order they were added :
+//   binding 2 * i : texture_2d<f32>
+//   binding 2 * i + 1 : sampler
+pub struct TextureBinder {
+    pub textures: Vec<texture::Texture>,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl TextureBinder {
+    pub fn bind(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        images: &[(image::DynamicImage, texture::SamplerConfig)],
+        sampler_pool: &mut texture::SamplerPool,
+    ) -> anyhow::Result<Option<Self>> {
+        if images.is_empty() {
+            return Ok(None);
+        }
+
+        let mut textures = Vec::new();
+        for (img, sampler_config) in images {
+            textures.push(texture::Texture::from_image_with_sampler_pool(
+                device,
+                queue,
+                img,
+                Some("extra_texture"),
+                sampler_config,
+                sampler_pool,
+            )?);
+        }
+
+        let mut layout_entries = Vec::new();
+        let mut entries = Vec::new();
+        for (index, texture) in textures.iter().enumerate() {
+            let binding = index as u32 * 2;
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            });
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: binding + 1,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler {
+                    comparison: false,
+                    filtering: true,
+                },
+                count: None,
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: binding + 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            });
+        }
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &layout_entries,
+            label: Some("texture_bind_group_layout"),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &entries,
+            label: Some("texture_bind_group"),
+        });
+
+        Ok(Some(TextureBinder {
+            textures,
+            bind_group_layout,
+            bind_group,
+        }))
+    }
 }
 
 pub struct ModelRenderer {
     pub model: Model,
+    // the pipelines & the bind groups are built from them, they can't change afterwards
+    render_options: RenderOptions,
     pub render_pipeline: wgpu::RenderPipeline,
+    // pipelines of the meshes drawn with another topology than TriangleList
+    pub topology_pipelines: Vec<(wgpu::PrimitiveTopology, wgpu::RenderPipeline)>,
     pub instance_buffer: Option<wgpu::Buffer>,
     pub instance_length: Option<usize>,
+    // instances the buffer can hold, grown by `update_instances`
+    pub instance_capacity: usize,
+    // CPU copy of the instances, used for their bounds
+    pub instances: Vec<InstanceRaw>,
+    // buffer & length of the custom instances
+    pub custom_instances: Option<(wgpu::Buffer, usize)>,
+    pub morph_binder: Option<MorphBinder>,
+    pub texture_binder: Option<TextureBinder>,
+    // lower detail models drawn from their camera distance, sorted by distance
+    pub lods: Vec<(f32, Model)>,
+    // index in `lods` of the drawn model, None for `model`
+    pub active_lod: Option<usize>,
+    // hidden objects are still updated but not drawn, picked or shadowed
+    pub visible: bool,
 }
 
 impl ModelRenderer {
+    #[allow(clippy::too_many_arguments)]
     pub fn new_renderer(
         model: Model,
         device: &wgpu::Device,
-        config: &wgpu::SurfaceConfiguration,
-        camera: &camera::Camera,
-        light: &light::Light,
+        target_formats: &TargetFormats,
+        // layouts of the bind groups shared by all the models (camera, light)
+        common_layouts: &[&wgpu::BindGroupLayout],
         shader_file: std::borrow::Cow<str>,
-        instance_data: Option<Vec<InstanceRaw>>,
-        instance_length: Option<usize>,
+        instances: Option<(Vec<InstanceRaw>, usize)>,
+        custom_instances: Option<CustomInstances>,
+        texture_binder: Option<TextureBinder>,
+        render_options: RenderOptions,
     ) -> ModelRenderer {
-        let instance_mode = if let Some(_) = &instance_data {
-            true
+        let mut model = model;
+        ModelRenderer::prepare_model(&mut model, device, &render_options);
+        // objects without instances are drawn as a single instance placed by `set_transform`,
+        // the custom instances & the water are placed by their shaders
+        let single_instance =
+            instances.is_none() && custom_instances.is_none() && render_options.water.is_none();
+        let instances = if single_instance {
+            Some((vec![InstanceRaw::new(cgmath::Matrix4::from_scale(1.0))], 1))
         } else {
-            false
+            instances
         };
+        let morph_binder = MorphBinder::bind(device, &model.meshes);
+        // the morph targets follow the material & common bind groups
+        let morph_group = morph_binder
+            .as_ref()
+            .map(|_| model.material_layout.is_some() as usize + common_layouts.len());
+        let shader_file = if render_options.wireframe {
+            ModelRenderer::wireframe_shader(&model, instances.is_some()).into()
+        } else if render_options.unlit {
+            ModelRenderer::unlit_shader(&model, instances.is_some(), morph_group).into()
+        } else if let Some(params) = render_options.water.as_ref() {
+            ModelRenderer::water_shader(&model, params).into()
+        } else {
+            shader_file
+        };
+        let instance_mode = instances.is_some();
 
-        let render_pipeline = {
+        let (render_pipeline, topology_pipelines) = {
             // declare a dynamic array for bind group layouts
             let mut bind_group_layouts = Vec::new();
             if let Some(material_layout) = model.material_layout.as_ref() {
                 bind_group_layouts.push(material_layout);
             }
             // add camera and lightning
-            bind_group_layouts.push(&camera.bind_group_layout);
-            bind_group_layouts.push(&light.bind_group_layout);
+            bind_group_layouts.extend_from_slice(common_layouts);
+            // add morph targets after the common bind groups
+            if let Some(morph_binder) = morph_binder.as_ref() {
+                bind_group_layouts.push(&morph_binder.bind_group_layout);
+            }
+            // then the extra textures of the object
+            if let Some(texture_binder) = texture_binder.as_ref() {
+                bind_group_layouts.push(&texture_binder.bind_group_layout);
+            }
 
             let render_pipeline_layout =
                 device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -314,114 +1488,470 @@ impl ModelRenderer {
                     bind_group_layouts: &bind_group_layouts[..],
                     push_constant_ranges: &[],
                 });
-            let shader = wgpu::ShaderModuleDescriptor {
+            let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
                 label: Some("Normal Shader"),
                 source: wgpu::ShaderSource::Wgsl(shader_file),
-            };
+            });
 
             let mut vertex_layouts = Vec::new();
             vertex_layouts.push(ModelVertex::desc());
             if instance_mode {
                 vertex_layouts.push(InstanceRaw::desc());
             }
+            if let Some(custom_instances) = custom_instances.as_ref() {
+                vertex_layouts.push(custom_instances.desc());
+            }
 
-            ModelRenderer::create_render_pipeline(
-                &device,
-                &render_pipeline_layout,
-                config.format,
-                Some(texture::Texture::DEPTH_FORMAT),
-                &vertex_layouts[..],
-                shader,
+            let create_pipeline = |topology| {
+                ModelRenderer::create_render_pipeline(
+                    device,
+                    &render_pipeline_layout,
+                    target_formats.color,
+                    Some(target_formats.depth),
+                    target_formats.sample_count,
+                    &vertex_layouts[..],
+                    &shader,
+                    topology,
+                    render_options,
+                )
+            };
+            let mut topology_pipelines = Vec::new();
+            for mesh in model.meshes.iter() {
+                let topology = mesh.geometry.topology;
+                if topology != wgpu::PrimitiveTopology::TriangleList
+                    && !topology_pipelines
+                        .iter()
+                        .any(|(pipeline_topology, _)| *pipeline_topology == topology)
+                {
+                    topology_pipelines.push((topology, create_pipeline(topology)));
+                }
+            }
+            (
+                create_pipeline(wgpu::PrimitiveTopology::TriangleList),
+                topology_pipelines,
             )
         };
 
-        let instance_buffer = if instance_mode {
-            Some(
+        let instance_usage = if render_options.dynamic_instances || single_instance {
+            wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST
+        } else {
+            wgpu::BufferUsages::VERTEX
+        };
+        let instance_copy = instances
+            .as_ref()
+            .map(|(instance_data, _)| instance_data.clone())
+            .unwrap_or_default();
+        let instance_capacity = instance_copy.len();
+        let (instance_buffer, instance_length) = if let Some((instance_data, length)) = instances {
+            (
+                Some(
+                    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("Instance Buffer"),
+                        contents: bytemuck::cast_slice(&instance_data),
+                        usage: instance_usage,
+                    }),
+                ),
+                Some(length).filter(|_| !single_instance),
+            )
+        } else {
+            (None, None)
+        };
+        let custom_instances = custom_instances.map(|custom_instances| {
+            (
                 device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    label: Some("Instance Buffer"),
-                    contents: bytemuck::cast_slice(&instance_data.unwrap()),
-                    usage: wgpu::BufferUsages::VERTEX,
+                    label: Some("Custom Instance Buffer"),
+                    contents: &custom_instances.data,
+                    usage: instance_usage,
                 }),
+                custom_instances.length,
             )
+        });
+
+        ModelRenderer {
+            model,
+            render_options,
+            render_pipeline,
+            topology_pipelines,
+            instance_buffer,
+            instance_length,
+            instance_capacity,
+            instances: instance_copy,
+            custom_instances,
+            morph_binder,
+            texture_binder,
+            lods: Vec::new(),
+            active_lod: None,
+            visible: true,
+        }
+    }
+
+    pub fn render_options(&self) -> &RenderOptions {
+        &self.render_options
+    }
+
+    // geometry changes needed by the render options
+    fn prepare_model(model: &mut Model, device: &wgpu::Device, render_options: &RenderOptions) {
+        if render_options.wireframe {
+            // the barycentric coordinates need separate triangles, other topologies are kept
+            for mesh in model.meshes.iter_mut() {
+                if mesh.geometry.topology == wgpu::PrimitiveTopology::TriangleList {
+                    mesh.geometry = mesh.geometry.unindexed(device);
+                }
+            }
+        }
+        if render_options.dynamic_vertices {
+            for mesh in model.meshes.iter_mut() {
+                mesh.geometry.set_dynamic(device);
+            }
+        }
+    }
+
+    // draw `model` in place of the object once the camera is `distance` away from its bounds.
+    // The LOD is drawn with the pipelines of the object, so it must have materials when the
+    // object has some, and the objects with morph targets have no LOD
+    pub fn add_lod(
+        &mut self,
+        device: &wgpu::Device,
+        distance: f32,
+        mut model: Model,
+    ) -> Result<(), ModelError> {
+        if self.morph_binder.is_some() {
+            return Err(ModelError::Lod("the object has morph targets"));
+        }
+        if model.material_layout.is_some() != self.model.material_layout.is_some() {
+            return Err(ModelError::Lod(
+                "the LOD and the object don't both have materials",
+            ));
+        }
+        ModelRenderer::prepare_model(&mut model, device, &self.render_options);
+        let index = self
+            .lods
+            .partition_point(|(lod_distance, _)| *lod_distance <= distance);
+        self.lods.insert(index, (distance, model));
+        Ok(())
+    }
+
+    // pick the LOD of the distance from `eye` to the closest instance
+    pub fn select_lod(&mut self, eye: cgmath::Point3<f32>) {
+        if self.lods.is_empty() {
+            return;
+        }
+        let distance = self
+            .world_bounds()
+            .iter()
+            .map(|bounds| bounds.distance(eye.into()))
+            .fold(f32::MAX, f32::min);
+        self.active_lod = self
+            .lods
+            .iter()
+            .rposition(|(lod_distance, _)| distance >= *lod_distance);
+    }
+
+    // the model drawn this frame, `model` or one of its LODs
+    pub fn drawn_model(&self) -> &Model {
+        match self.active_lod {
+            Some(index) => &self.lods[index].1,
+            None => &self.model,
+        }
+    }
+
+    fn wireframe_shader(model: &Model, instanced: bool) -> String {
+        // the camera follows the material bind group when there is one
+        let camera_group = model.material_layout.is_some() as u32;
+        let vertex_stage = if instanced {
+            include_str!("shader/wireframe_instanced.wgsl")
         } else {
-            None
+            include_str!("shader/wireframe_vertex.wgsl")
         };
+        format!(
+            "{}\n{}",
+            include_str!("shader/wireframe.wgsl"),
+            vertex_stage
+        )
+        .replace("CAMERA_GROUP", &camera_group.to_string())
+    }
 
-        ModelRenderer {
-            model: model,
-            render_pipeline: render_pipeline,
-            instance_buffer: instance_buffer,
-            instance_length: instance_length,
+    fn unlit_shader(model: &Model, instanced: bool, morph_group: Option<usize>) -> String {
+        let (camera_group, fragment_stage) = if model.material_layout.is_some() {
+            (1, include_str!("shader/unlit_textured.wgsl"))
+        } else {
+            (0, include_str!("shader/unlit_flat.wgsl"))
+        };
+        let vertex_stage = if instanced {
+            include_str!("shader/unlit_instanced.wgsl")
+        } else {
+            include_str!("shader/unlit_vertex.wgsl")
+        };
+        let morph = match morph_group {
+            Some(morph_group) => MORPH_WGSL.replace("MORPH_GROUP", &morph_group.to_string()),
+            None => include_str!("shader/morph_none.wgsl").to_string(),
+        };
+        format!(
+            "{}\n{}\n{}\n{}",
+            include_str!("shader/unlit.wgsl"),
+            morph,
+            vertex_stage,
+            fragment_stage
+        )
+        .replace("CAMERA_GROUP", &camera_group.to_string())
+    }
+
+    fn water_shader(model: &Model, params: &water::WaterParams) -> String {
+        let camera_group = model.material_layout.is_some() as u32;
+        water::shader(params).replace("CAMERA_GROUP", &camera_group.to_string())
+    }
+
+    // the common bind groups this renderer binds, unlit ones skip the light
+    fn common_bind_groups<'b>(
+        &self,
+        bind_groups: &[&'b wgpu::BindGroup],
+    ) -> Vec<&'b wgpu::BindGroup> {
+        bind_groups
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !(self.render_options.unlit && *index == LIGHT_GROUP))
+            .map(|(_, bind_group)| *bind_group)
+            .collect()
+    }
+
+    // bounds of the drawn instances in world space, one for each instance
+    pub fn world_bounds(&self) -> Vec<Aabb> {
+        let bounds = match self.model.bounds() {
+            Some(bounds) => bounds,
+            None => return Vec::new(),
+        };
+        if self.instances.is_empty() {
+            return vec![bounds];
+        }
+        if !self.is_instanced() {
+            return vec![bounds.transformed(self.transform())];
+        }
+        self.instances
+            .iter()
+            .take(self.instance_count())
+            .map(|instance| bounds.transformed(instance.model.into()))
+            .collect()
+    }
+
+    // opaque objects whose transforms are known on the CPU, the custom instances are only
+    // placed by the shaders
+    pub fn casts_shadows(&self) -> bool {
+        self.render_options.casts_shadows
+            && !self.render_options.transparent
+            && !self.render_options.background
+            && self.custom_instances.is_none()
+    }
+
+    // the vertices are drawn where they were loaded : not moved by instances, morph targets
+    // or writes to a dynamic vertex buffer, so the bounds of the meshes can be culled
+    pub fn is_cullable(&self) -> bool {
+        self.render_options.culling
+            && self.render_options.water.is_none()
+            && !self.is_instanced()
+            && self.morph_binder.is_none()
+            && !self.render_options.dynamic_vertices
+    }
+
+    // model matrix of an object drawn as a single instance, identity for the others
+    pub fn transform(&self) -> cgmath::Matrix4<f32> {
+        match self.instances.first() {
+            Some(instance) if !self.is_instanced() => instance.model.into(),
+            _ => cgmath::Matrix4::from_scale(1.0),
         }
     }
 
+    // place an object created without instances. Its shader reads the model matrix at
+    // locations 5 to 8 like the instanced ones, see `InstanceRaw`. The instances of the other
+    // objects are replaced by `update_instances`
+    pub fn set_transform(&mut self, queue: &wgpu::Queue, transform: cgmath::Matrix4<f32>) {
+        let instance_buffer = match self.instance_buffer.as_ref() {
+            Some(instance_buffer) if !self.is_instanced() => instance_buffer,
+            _ => {
+                log::warn!("only the objects created without instances can be transformed");
+                return;
+            }
+        };
+        self.instances = vec![InstanceRaw::new(transform)];
+        queue.write_buffer(instance_buffer, 0, bytemuck::cast_slice(&self.instances));
+    }
+
+    pub fn is_instanced(&self) -> bool {
+        self.instance_length.is_some() || self.custom_instances.is_some()
+    }
+
+    // number of instances drawn, 1 when the model is not instanced
+    pub fn instance_count(&self) -> usize {
+        match (self.instance_length, self.custom_instances.as_ref()) {
+            (Some(length), Some((_, custom_length))) => length.min(*custom_length),
+            (Some(length), None) => length,
+            (None, Some((_, custom_length))) => *custom_length,
+            (None, None) => 1,
+        }
+    }
+
+    // replace the drawn instances, e.g. to move a swarm each frame. The buffer is written in
+    // place while the instances fit in it, it is recreated with room to grow otherwise.
+    // Objects with custom instances only & water have no instance buffer in their pipeline,
+    // they are left unchanged
+    pub fn update_instances(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        instances: &[InstanceRaw],
+    ) {
+        if self.instance_buffer.is_none() {
+            log::warn!("the object has no instance buffer to update");
+            return;
+        }
+        // static buffers have no COPY_DST, they are replaced by a dynamic one
+        if instances.len() > self.instance_capacity || !self.render_options.dynamic_instances {
+            self.instance_capacity = instances
+                .len()
+                .max(self.instance_capacity)
+                .next_power_of_two();
+            self.instance_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Instance Buffer"),
+                size: (self.instance_capacity * std::mem::size_of::<InstanceRaw>())
+                    as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+            self.render_options.dynamic_instances = true;
+        }
+        if let Some(instance_buffer) = self.instance_buffer.as_ref() {
+            queue.write_buffer(instance_buffer, 0, bytemuck::cast_slice(instances));
+        }
+        self.instance_length = Some(instances.len());
+        self.instances = instances.to_vec();
+    }
+
+    pub fn set_morph_weights(&mut self, queue: &wgpu::Queue, weights: &[f32]) {
+        if let Some(morph_binder) = self.morph_binder.as_mut() {
+            let uniform = &mut morph_binder.morph_uniform;
+            for (index, weight) in weights.iter().take(uniform.count as usize).enumerate() {
+                uniform.weights[index / 4][index % 4] = *weight;
+            }
+            queue.write_buffer(
+                &morph_binder.weights_buffer,
+                0,
+                bytemuck::cast_slice(&[*uniform]),
+            );
+        }
+    }
+
+    // pipeline of the meshes drawn with `topology`
+    pub fn pipeline(&self, topology: wgpu::PrimitiveTopology) -> &wgpu::RenderPipeline {
+        self.topology_pipelines
+            .iter()
+            .find(|(pipeline_topology, _)| *pipeline_topology == topology)
+            .map_or(&self.render_pipeline, |(_, pipeline)| pipeline)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn create_render_pipeline(
         device: &wgpu::Device,
         layout: &wgpu::PipelineLayout,
         color_format: wgpu::TextureFormat,
         depth_format: Option<wgpu::TextureFormat>,
+        sample_count: u32,
         vertex_layouts: &[wgpu::VertexBufferLayout],
-        shader: wgpu::ShaderModuleDescriptor,
+        shader: &wgpu::ShaderModule,
+        topology: wgpu::PrimitiveTopology,
+        render_options: RenderOptions,
     ) -> wgpu::RenderPipeline {
-        let shader = device.create_shader_module(&shader);
-
         device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("Render Pipeline"),
             layout: Some(layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: "vs_main",
                 buffers: vertex_layouts,
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
+                module: shader,
                 entry_point: "fs_main",
                 targets: &[wgpu::ColorTargetState {
                     format: color_format,
-                    blend: Some(wgpu::BlendState {
-                        alpha: wgpu::BlendComponent::REPLACE,
-                        color: wgpu::BlendComponent::REPLACE,
-                    }),
+                    blend: Some(render_options.blend),
                     write_mask: wgpu::ColorWrites::ALL,
                 }],
             }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLAMPING
-                clamp_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
+            primitive: primitive_state(topology, &render_options),
             depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
                 format,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
+                depth_write_enabled: render_options.depth_write_enabled,
+                depth_compare: render_options.depth_compare,
+                stencil: render_options
+                    .stencil
+                    .map(|stencil| stencil.state())
+                    .unwrap_or_default(),
+                bias: render_options.depth_bias,
             }),
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: sample_count,
                 mask: !0,
-                alpha_to_coverage_enabled: false,
+                alpha_to_coverage_enabled: render_options.alpha_to_coverage,
             },
         })
     }
 }
 
+// the back faces are culled, front faces are wound like `render_options.front_face`
+fn primitive_state(
+    topology: wgpu::PrimitiveTopology,
+    render_options: &RenderOptions,
+) -> wgpu::PrimitiveState {
+    wgpu::PrimitiveState {
+        topology,
+        // the index buffers are u32, PRIMITIVE_RESTART ends a strip
+        strip_index_format: topology.is_strip().then_some(wgpu::IndexFormat::Uint32),
+        front_face: render_options.front_face,
+        cull_mode: Some(wgpu::Face::Back),
+        // Line & Point require Features::POLYGON_MODE_LINE & POLYGON_MODE_POINT
+        polygon_mode: render_options.polygon_mode,
+        // Requires Features::DEPTH_CLAMPING
+        clamp_depth: false,
+        // Requires Features::CONSERVATIVE_RASTERIZATION
+        conservative: false,
+    }
+}
+
+// GPU resources owned by the scene
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ResourceStats {
+    pub materials: usize,
+    pub samplers: usize,
+}
+
+// counters of the last rendered frame
+#[derive(Debug, Default, Copy, Clone)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    pub triangles: u64,
+    pub instances: u64,
+    // meshes skipped because they were out of the view
+    pub culled: u32,
+}
+
+// index of the light in the common bind groups, after the camera
+pub const LIGHT_GROUP: usize = 1;
+
 pub trait DrawModel<'a> {
-    fn draw_model(&mut self, model: &'a ModelRenderer, bind_groups: &'a [&'a wgpu::BindGroup]);
+    fn draw_model(
+        &mut self,
+        model: &'a ModelRenderer,
+        bind_groups: &'a [&'a wgpu::BindGroup],
+        frustum: Option<&Frustum>,
+        stats: &mut RenderStats,
+    );
 
     fn draw_mesh_instanced(
         &mut self,
         mesh: &'a Mesh,
         material_bind_group: Option<&'a wgpu::BindGroup>,
         instances: Range<u32>,
-        bind_groups: &'a [&'a wgpu::BindGroup],
+        bind_groups: &[&'a wgpu::BindGroup],
+        stats: &mut RenderStats,
     );
 }
 
@@ -429,40 +1959,63 @@ impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a>
 where
     'b: 'a,
 {
+    // the meshes out of `frustum` are skipped when their bounds are known, see
+    // `ModelRenderer::is_cullable`
     fn draw_model(
         &mut self,
         model_renderer: &'b ModelRenderer,
         bind_groups: &'b [&'b wgpu::BindGroup],
+        frustum: Option<&Frustum>,
+        stats: &mut RenderStats,
     ) {
-        // set pipeline
-        self.set_pipeline(&model_renderer.render_pipeline);
-
-        // check if there is more than one instance to draw
-        let instances_to_draw = if let Some(instance_range) = model_renderer.instance_length {
-            // set the instance buffer
-            self.set_vertex_buffer(
-                1,
-                model_renderer.instance_buffer.as_ref().unwrap().slice(..),
-            );
-            // return the instances range
-            0..(instance_range as u32)
-        } else {
-            0..1
-        };
+        let frustum = frustum.filter(|_| model_renderer.is_cullable());
+        // set the instance buffers, the custom one follows the transforms
+        let mut slot = 1;
+        if let Some(instance_buffer) = model_renderer.instance_buffer.as_ref() {
+            self.set_vertex_buffer(slot, instance_buffer.slice(..));
+            slot += 1;
+        }
+        if let Some((custom_buffer, _)) = model_renderer.custom_instances.as_ref() {
+            self.set_vertex_buffer(slot, custom_buffer.slice(..));
+        }
+        let instances_to_draw = 0..model_renderer.instance_count() as u32;
+        let bind_groups = &model_renderer.common_bind_groups(bind_groups)[..];
+
+        // morph targets are bound after the material and common bind groups
+        let model = model_renderer.drawn_model();
+        let morph_index = bind_groups.len() as u32 + model.material_layout.is_some() as u32;
+        if let Some(texture_binder) = model_renderer.texture_binder.as_ref() {
+            let texture_index = morph_index + model_renderer.morph_binder.is_some() as u32;
+            self.set_bind_group(texture_index, &texture_binder.bind_group, &[]);
+        }
 
         // draw each mesh of the model
-        for mesh in &model_renderer.model.meshes {
+        for (mesh_index, mesh) in model.meshes.iter().enumerate() {
+            if let (Some(frustum), Some(bounds)) = (frustum, mesh.geometry.bounds.as_ref()) {
+                if !frustum.intersects(&bounds.transformed(model_renderer.transform())) {
+                    stats.culled += 1;
+                    continue;
+                }
+            }
+            // the meshes of a model may have different topologies
+            self.set_pipeline(model_renderer.pipeline(mesh.geometry.topology));
+
+            if let Some(morph_binder) = model_renderer.morph_binder.as_ref() {
+                self.set_bind_group(morph_index, &morph_binder.bind_groups[mesh_index], &[]);
+            }
+
             if let Some(material_index) = mesh.material_id {
                 let material_bind_group =
-                    &model_renderer.model.materials.as_ref().unwrap()[material_index].bind_group;
+                    &model.materials.as_ref().unwrap()[material_index].bind_group;
                 self.draw_mesh_instanced(
                     mesh,
                     Some(material_bind_group),
                     instances_to_draw.clone(),
                     bind_groups,
+                    stats,
                 );
             } else {
-                self.draw_mesh_instanced(mesh, None, instances_to_draw.clone(), bind_groups);
+                self.draw_mesh_instanced(mesh, None, instances_to_draw.clone(), bind_groups, stats);
             }
         }
     }
@@ -472,7 +2025,8 @@ where
         mesh: &'b Mesh,
         material_bind_group: Option<&'b wgpu::BindGroup>,
         instances: Range<u32>,
-        bind_groups: &'b [&'b wgpu::BindGroup],
+        bind_groups: &[&'b wgpu::BindGroup],
+        stats: &mut RenderStats,
     ) {
         // set vertex & index buffer
         self.set_vertex_buffer(0, mesh.geometry.vertex_buffer.slice(..));
@@ -490,10 +2044,35 @@ where
 
         // set light bind group
         bind_groups.iter().enumerate().for_each(|(index, group)| {
-            self.set_bind_group(index as u32 + offset, *group, &[]);
+            self.set_bind_group(index as u32 + offset, group, &[]);
         });
 
         // draw the mesh
+        let instance_count = (instances.end - instances.start) as u64;
+        stats.draw_calls += 1;
+        stats.instances += instance_count;
+        stats.triangles += mesh.geometry.triangle_count() * instance_count;
         self.draw_indexed(0..mesh.geometry.num_elements, 0, instances);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn front_face_reaches_the_primitive_state() {
+        let topology = wgpu::PrimitiveTopology::TriangleList;
+        let default = primitive_state(topology, &RenderOptions::default());
+        assert_eq!(default.front_face, wgpu::FrontFace::Ccw);
+        assert_eq!(default.cull_mode, Some(wgpu::Face::Back));
+
+        let render_options = RenderOptions {
+            front_face: wgpu::FrontFace::Cw,
+            ..Default::default()
+        };
+        let cw = primitive_state(topology, &render_options);
+        assert_eq!(cw.front_face, wgpu::FrontFace::Cw);
+        assert_eq!(cw.cull_mode, Some(wgpu::Face::Back));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{fullscreen, texture};
+
+// Opaque objects are rendered in an offscreen color target, copied on the surface,
+// then objects sampling the scene color are drawn with it bound after the common bind groups :
+//   binding 0 : texture_2d<f32> of the opaque scene
+//   binding 1 : sampler
+pub struct SceneColor {
+    pub texture: texture::Texture,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    blit: fullscreen::FullscreenPass,
+    blit_bind_group: wgpu::BindGroup,
+}
+
+impl SceneColor {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let texture = texture::Texture::create_render_target(device, config, "scene_color");
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("scene_color_bind_group_layout"),
+        });
+        let bind_group = SceneColor::create_bind_group(device, &bind_group_layout, &texture);
+
+        let blit = fullscreen::FullscreenPass::new(
+            device,
+            include_str!("shader/blit.wgsl"),
+            &[wgpu::TextureSampleType::Float { filterable: true }],
+            config.format,
+        );
+        let blit_bind_group = blit.create_bind_group(device, &[&texture.view]);
+
+        SceneColor {
+            texture,
+            bind_group_layout,
+            bind_group,
+            blit,
+            blit_bind_group,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        texture: &texture::Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: Some("scene_color_bind_group"),
+        })
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        self.texture = texture::Texture::create_render_target(device, config, "scene_color");
+        self.bind_group =
+            SceneColor::create_bind_group(device, &self.bind_group_layout, &self.texture);
+        self.blit_bind_group = self.blit.create_bind_group(device, &[&self.texture.view]);
+    }
+
+    // copy the scene color on the target
+    pub fn blit(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        self.blit.draw(encoder, target, &self.blit_bind_group);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::renderer::ModelRenderer;
+
+// name of the scene receiving the objects of `Lens::add_object`
+pub const DEFAULT_SCENE: &str = "default";
+
+// object of a scene, its index in the scene it was added to, in the order the objects were
+// added. Removed objects leave their slot empty so that the other ids stay valid
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ObjectId {
+    scene: usize,
+    index: usize,
+}
+
+impl ObjectId {
+    pub(crate) fn new(scene: usize, index: usize) -> Self {
+        ObjectId { scene, index }
+    }
+
+    // order of the object in its scene
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+// sets of objects sharing the GPU context, only the active one is drawn
+pub struct SceneManager {
+    scenes: Vec<(String, Vec<Option<ModelRenderer>>)>,
+    active: usize,
+}
+
+impl SceneManager {
+    pub fn new() -> Self {
+        SceneManager {
+            scenes: vec![(DEFAULT_SCENE.to_string(), Vec::new())],
+            active: 0,
+        }
+    }
+
+    // renderers are added to the scene with the same name if it exists
+    pub fn add_scene(&mut self, name: &str, renderers: Vec<ModelRenderer>) {
+        match self
+            .scenes
+            .iter_mut()
+            .find(|(scene_name, _)| scene_name == name)
+        {
+            Some((_, scene_renderers)) => scene_renderers.extend(renderers.into_iter().map(Some)),
+            None => self
+                .scenes
+                .push((name.to_string(), renderers.into_iter().map(Some).collect())),
+        }
+    }
+
+    // returns false if no scene has this name
+    pub fn activate_scene(&mut self, name: &str) -> bool {
+        match self
+            .scenes
+            .iter()
+            .position(|(scene_name, _)| scene_name == name)
+        {
+            Some(index) => {
+                self.active = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn active_scene(&self) -> &str {
+        &self.scenes[self.active].0
+    }
+
+    // objects of the active scene that weren't removed, hidden ones included
+    pub fn objects(&self) -> impl Iterator<Item = (ObjectId, &ModelRenderer)> {
+        self.scenes[self.active]
+            .1
+            .iter()
+            .enumerate()
+            .filter_map(|(index, renderer)| {
+                Some((ObjectId::new(self.active, index), renderer.as_ref()?))
+            })
+    }
+
+    pub fn renderers(&self) -> impl Iterator<Item = &ModelRenderer> {
+        self.scenes[self.active].1.iter().flatten()
+    }
+
+    pub fn renderers_mut(&mut self) -> impl Iterator<Item = &mut ModelRenderer> {
+        self.scenes[self.active].1.iter_mut().flatten()
+    }
+
+    // renderers of the active scene to draw, the visible ones
+    pub fn visible_renderers(&self) -> impl Iterator<Item = &ModelRenderer> {
+        self.renderers().filter(|renderer| renderer.visible)
+    }
+
+    // the object is looked up in its own scene, active or not. None once removed or for the
+    // id of another scene manager
+    pub fn get(&self, id: ObjectId) -> Option<&ModelRenderer> {
+        self.scenes.get(id.scene)?.1.get(id.index)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, id: ObjectId) -> Option<&mut ModelRenderer> {
+        self.scenes.get_mut(id.scene)?.1.get_mut(id.index)?.as_mut()
+    }
+
+    // the slot of the object stays empty, its GPU resources are released with the renderer
+    pub fn remove(&mut self, id: ObjectId) -> Option<ModelRenderer> {
+        self.scenes.get_mut(id.scene)?.1.get_mut(id.index)?.take()
+    }
+
+    // renderers of every scene, active or not
+    pub fn all_renderers(&self) -> impl Iterator<Item = &ModelRenderer> {
+        self.scenes
+            .iter()
+            .flat_map(|(_, renderers)| renderers.iter().flatten())
+    }
+}
+
+impl Default for SceneManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,9 @@
+[[group(0), binding(0)]]
+var s_input: sampler;
+[[group(0), binding(1)]]
+var t_input: texture_2d<f32>;
+
+[[stage(fragment)]]
+fn fs_main(vertex: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(t_input, s_input, vertex.tex_coords);
+}
@@ -0,0 +1,61 @@
+// lights of the cluster of a fragment, bound after the shadow map of the light group
+
+[[block]]
+struct LightClusters {
+    view: mat4x4<f32>;
+    view_proj: mat4x4<f32>;
+    inverse_projection: mat4x4<f32>;
+    // clusters along x, y & z, max lights per cluster in w
+    size: vec4<u32>;
+    near: f32;
+    far: f32;
+};
+
+// for each cluster its light count followed by `size.w` light indices
+[[block]]
+struct ClusterLights {
+    data: array<u32>;
+};
+
+[[group(LIGHT_GROUP), binding(5)]]
+var<uniform> light_clusters: LightClusters;
+[[group(LIGHT_GROUP), binding(6)]]
+var<storage, read> cluster_lights: ClusterLights;
+
+// fragments outside of the clustered view are lit by all the lights
+let NO_CLUSTER: u32 = 4294967295u;
+
+// offset of the cluster around `world_position` in `cluster_lights`
+fn light_cluster(world_position: vec3<f32>) -> u32 {
+    let size = light_clusters.size;
+    let clip = light_clusters.view_proj * vec4<f32>(world_position, 1.0);
+    let depth = -(light_clusters.view * vec4<f32>(world_position, 1.0)).z;
+    if (clip.w <= 0.0 || depth < light_clusters.near || depth > light_clusters.far) {
+        return NO_CLUSTER;
+    }
+    let ndc = clip.xy / clip.w;
+    if (abs(ndc.x) > 1.0 || abs(ndc.y) > 1.0) {
+        return NO_CLUSTER;
+    }
+    let x = min(u32((ndc.x * 0.5 + 0.5) * f32(size.x)), size.x - 1u);
+    let y = min(u32((ndc.y * 0.5 + 0.5) * f32(size.y)), size.y - 1u);
+    let slices = log(depth / light_clusters.near) / log(light_clusters.far / light_clusters.near);
+    let z = min(u32(slices * f32(size.z)), size.z - 1u);
+    return ((z * size.y + y) * size.x + x) * (size.w + 1u);
+}
+
+// lights to iterate in the cluster, `light_count` of them outside of the clustered view
+fn cluster_light_count(cluster: u32, light_count: u32) -> u32 {
+    if (cluster == NO_CLUSTER) {
+        return light_count;
+    }
+    return cluster_lights.data[cluster];
+}
+
+// index in the lights of the `i`th light of the cluster
+fn cluster_light(cluster: u32, i: u32) -> u32 {
+    if (cluster == NO_CLUSTER) {
+        return i;
+    }
+    return cluster_lights.data[cluster + 1u + i];
+}
@@ -0,0 +1,36 @@
+// Linearized depth as grayscale, black at the near plane and white at the far plane
+
+[[group(0), binding(0)]]
+var s_input: sampler;
+[[group(0), binding(1)]]
+var t_depth: texture_depth_2d;
+
+[[block]]
+struct DepthDebugUniform {
+    near: f32;
+    far: f32;
+    // 1 for an orthographic projection, where the depth is already linear
+    orthographic: f32;
+};
+[[group(1), binding(0)]]
+var<uniform> depth_debug: DepthDebugUniform;
+
+[[stage(fragment)]]
+fn fs_main(vertex: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let dimensions = textureDimensions(t_depth);
+    let pixel = clamp(
+        vec2<i32>(vertex.tex_coords * vec2<f32>(dimensions)),
+        vec2<i32>(0),
+        dimensions - vec2<i32>(1),
+    );
+    let depth = textureLoad(t_depth, pixel, 0);
+
+    let near = depth_debug.near;
+    let far = depth_debug.far;
+    var distance = near * far / (far - depth * (far - near));
+    if (depth_debug.orthographic > 0.5) {
+        distance = near + depth * (far - near);
+    }
+    let gray = clamp((distance - near) / (far - near), 0.0, 1.0);
+    return vec4<f32>(gray, gray, gray, 1.0);
+}
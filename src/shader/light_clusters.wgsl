@@ -0,0 +1,111 @@
+// assigns the lights to the clusters of the view, one invocation per cluster
+
+struct Light {
+    position: vec3<f32>;
+    kind: u32;
+    color: vec3<f32>;
+    range: f32;
+    direction: vec3<f32>;
+    cos_inner: f32;
+    cos_outer: f32;
+};
+
+[[block]]
+struct Lights {
+    count: u32;
+    ambient: vec3<f32>;
+    lights: array<Light>;
+};
+
+[[block]]
+struct LightClusters {
+    view: mat4x4<f32>;
+    view_proj: mat4x4<f32>;
+    inverse_projection: mat4x4<f32>;
+    // clusters along x, y & z, max lights per cluster in w
+    size: vec4<u32>;
+    near: f32;
+    far: f32;
+};
+
+// for each cluster its light count followed by `size.w` light indices
+[[block]]
+struct ClusterLights {
+    data: array<u32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> light_clusters: LightClusters;
+[[group(0), binding(1)]]
+var<storage, read> lights: Lights;
+[[group(0), binding(2)]]
+var<storage, read_write> cluster_lights: ClusterLights;
+
+// view space point of a corner of the tile at the depth plane of the clip space `depth`
+fn unproject(ndc: vec2<f32>, depth: f32) -> vec3<f32> {
+    let view = light_clusters.inverse_projection * vec4<f32>(ndc, depth, 1.0);
+    return view.xyz / view.w;
+}
+
+// distance to the camera of the near plane of a depth slice, exponential between near & far
+fn slice_depth(slice: u32) -> f32 {
+    let t = f32(slice) / f32(light_clusters.size.z);
+    return light_clusters.near * pow(light_clusters.far / light_clusters.near, t);
+}
+
+[[stage(compute), workgroup_size(64)]]
+fn cs_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    let size = light_clusters.size;
+    let cluster = id.x;
+    if (cluster >= size.x * size.y * size.z) {
+        return;
+    }
+    let x = cluster % size.x;
+    let y = (cluster / size.x) % size.y;
+    let z = cluster / (size.x * size.y);
+
+    let ndc_min = vec2<f32>(f32(x) / f32(size.x), f32(y) / f32(size.y)) * 2.0 - 1.0;
+    let ndc_max = vec2<f32>(f32(x + 1u) / f32(size.x), f32(y + 1u) / f32(size.y)) * 2.0 - 1.0;
+    let depth_min = slice_depth(z);
+    let depth_max = slice_depth(z + 1u);
+
+    // view space bounds of the corners of the tile between the depths of the slice, the
+    // camera looks toward -Z
+    var bounds_min = vec3<f32>(1.0e30, 1.0e30, 1.0e30);
+    var bounds_max = vec3<f32>(-1.0e30, -1.0e30, -1.0e30);
+    for (var corner = 0u; corner < 4u; corner = corner + 1u) {
+        let ndc = vec2<f32>(
+            select(ndc_min.x, ndc_max.x, (corner & 1u) != 0u),
+            select(ndc_min.y, ndc_max.y, (corner & 2u) != 0u),
+        );
+        let near_point = unproject(ndc, 0.0);
+        let far_point = unproject(ndc, 1.0);
+        let ray = far_point - near_point;
+        let near_corner = near_point + ray * ((-depth_min - near_point.z) / ray.z);
+        let far_corner = near_point + ray * ((-depth_max - near_point.z) / ray.z);
+        bounds_min = min(bounds_min, min(near_corner, far_corner));
+        bounds_max = max(bounds_max, max(near_corner, far_corner));
+    }
+
+    let offset = cluster * (size.w + 1u);
+    var count = 0u;
+    for (var i = 0u; i < lights.count; i = i + 1u) {
+        if (count == size.w) {
+            break;
+        }
+        let light = lights.lights[i];
+        // directional lights & lights without range reach every cluster, the others are
+        // bounded by the sphere of their range
+        var reaches = light.kind == 1u || light.range <= 0.0;
+        if (!reaches) {
+            let center = (light_clusters.view * vec4<f32>(light.position, 1.0)).xyz;
+            let offset_to_bounds = clamp(center, bounds_min, bounds_max) - center;
+            reaches = dot(offset_to_bounds, offset_to_bounds) <= light.range * light.range;
+        }
+        if (reaches) {
+            cluster_lights.data[offset + 1u + count] = i;
+            count = count + 1u;
+        }
+    }
+    cluster_lights.data[offset] = count;
+}
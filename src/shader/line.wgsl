@@ -0,0 +1,71 @@
+// Lines are drawn as screen-space quads, one instance per line
+
+[[block]]
+struct Camera {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> camera: Camera;
+
+[[block]]
+struct LineUniform {
+    viewport: vec2<f32>;
+    width: f32;
+};
+[[group(1), binding(0)]]
+var<uniform> line: LineUniform;
+
+struct LineInput {
+    [[location(0)]] start: vec3<f32>;
+    [[location(1)]] end: vec3<f32>;
+    [[location(2)]] color: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+    // distance to the center of the line, in pixels
+    [[location(1)]] edge: f32;
+};
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32, line_in: LineInput) -> VertexOutput {
+    // two triangles : (start-, start+, end-) and (end-, start+, end+)
+    var ends = array<f32, 6>(0.0, 0.0, 1.0, 1.0, 0.0, 1.0);
+    var sides = array<f32, 6>(-1.0, 1.0, -1.0, -1.0, 1.0, 1.0);
+    let end = ends[index % 6u];
+    let side = sides[index % 6u];
+
+    let clip_start = camera.view_proj * vec4<f32>(line_in.start, 1.0);
+    let clip_end = camera.view_proj * vec4<f32>(line_in.end, 1.0);
+    let half_viewport = line.viewport * 0.5;
+    let screen_start = clip_start.xy / clip_start.w * half_viewport;
+    let screen_end = clip_end.xy / clip_end.w * half_viewport;
+
+    var direction = vec2<f32>(1.0, 0.0);
+    if (distance(screen_start, screen_end) > 0.0001) {
+        direction = normalize(screen_end - screen_start);
+    }
+    let normal = vec2<f32>(-direction.y, direction.x);
+
+    // one more pixel on each side for the smooth edges
+    let half_width = line.width * 0.5 + 1.0;
+
+    var clip = mix(clip_start, clip_end, end);
+    let offset = normal * side * half_width / half_viewport * clip.w;
+    clip = vec4<f32>(clip.xy + offset, clip.z, clip.w);
+
+    var out: VertexOutput;
+    out.clip_position = clip;
+    out.color = line_in.color;
+    out.edge = side * half_width;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(vertex: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // fade the last pixel of each side of the line
+    let coverage = clamp(line.width * 0.5 + 0.5 - abs(vertex.edge), 0.0, 1.0);
+    return vec4<f32>(vertex.color.rgb, vertex.color.a * coverage);
+}
@@ -0,0 +1,37 @@
+// Blending of the morph targets by their weights, MORPH_GROUP is replaced by the index of the
+// morph bind group. Prepended to the vertex stages, which move the vertices with
+// `morph_position` & `morph_normal` and the `[[builtin(vertex_index)]]` of the vertex.
+
+[[block]]
+struct MorphWeights {
+    weights: array<vec4<f32>, 2>;
+    count: u32;
+};
+[[group(MORPH_GROUP), binding(0)]]
+var<uniform> morph: MorphWeights;
+
+[[block]]
+struct MorphDeltas {
+    // position then normal delta of each target, for each vertex
+    deltas: array<vec4<f32>>;
+};
+[[group(MORPH_GROUP), binding(1)]]
+var<storage, read> morph_deltas: MorphDeltas;
+
+// sum of the weighted deltas, 0 for the positions & 1 for the normals
+fn morph_delta(vertex_index: u32, kind: u32) -> vec3<f32> {
+    var sum: vec3<f32> = vec3<f32>(0.0);
+    for (var i: u32 = 0u; i < morph.count; i = i + 1u) {
+        let weight = morph.weights[i / 4u][i % 4u];
+        sum = sum + morph_deltas.deltas[(vertex_index * morph.count + i) * 2u + kind].xyz * weight;
+    }
+    return sum;
+}
+
+fn morph_position(position: vec3<f32>, vertex_index: u32) -> vec3<f32> {
+    return position + morph_delta(vertex_index, 0u);
+}
+
+fn morph_normal(normal: vec3<f32>, vertex_index: u32) -> vec3<f32> {
+    return normalize(normal + morph_delta(vertex_index, 1u));
+}
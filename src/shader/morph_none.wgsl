@@ -0,0 +1,8 @@
+// stands in for morph.wgsl when the model has no morph target
+fn morph_position(position: vec3<f32>, vertex_index: u32) -> vec3<f32> {
+    return position;
+}
+
+fn morph_normal(normal: vec3<f32>, vertex_index: u32) -> vec3<f32> {
+    return normal;
+}
@@ -0,0 +1,42 @@
+// Screen-space textured quad, positioned in pixels from the top-left corner
+
+[[block]]
+struct OverlayUniform {
+    // x, y, width, height in pixels
+    rect: vec4<f32>;
+    viewport: vec2<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> overlay: OverlayUniform;
+[[group(0), binding(1)]]
+var t_overlay: texture_2d<f32>;
+[[group(0), binding(2)]]
+var s_overlay: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    var xs = array<f32, 6>(0.0, 0.0, 1.0, 1.0, 0.0, 1.0);
+    var ys = array<f32, 6>(0.0, 1.0, 0.0, 0.0, 1.0, 1.0);
+    let uv = vec2<f32>(xs[index % 6u], ys[index % 6u]);
+
+    let pixel = overlay.rect.xy + uv * overlay.rect.zw;
+    let ndc = vec2<f32>(
+        pixel.x / overlay.viewport.x * 2.0 - 1.0,
+        1.0 - pixel.y / overlay.viewport.y * 2.0,
+    );
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.tex_coords = uv;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(vertex: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(t_overlay, s_overlay, vertex.tex_coords);
+}
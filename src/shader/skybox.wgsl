@@ -0,0 +1,38 @@
+// Skybox : a triangle covering the screen on the far plane, each pixel samples the cubemap
+// in the direction of its view ray
+
+[[block]]
+struct Skybox {
+    inv_view_proj: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> skybox: Skybox;
+[[group(0), binding(1)]]
+var t_skybox: texture_cube<f32>;
+[[group(0), binding(2)]]
+var s_skybox: sampler;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] ndc: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    // (-1, -1), (3, -1), (-1, 3) cover the whole screen
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.ndc = uv * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(out.ndc, 1.0, 1.0);
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // two points of the view ray, also valid for an orthographic camera
+    let near = skybox.inv_view_proj * vec4<f32>(in.ndc, 0.0, 1.0);
+    let far = skybox.inv_view_proj * vec4<f32>(in.ndc, 0.5, 1.0);
+    let direction = far.xyz / far.w - near.xyz / near.w;
+    return textureSample(t_skybox, s_skybox, direction);
+}
@@ -0,0 +1,61 @@
+// Temporal anti-aliasing resolve : the jittered frame is blended with the history
+// reprojected by the camera motion, the history is clamped to the current neighborhood.
+
+[[group(0), binding(0)]]
+var s_input: sampler;
+[[group(0), binding(1)]]
+var t_current: texture_2d<f32>;
+[[group(0), binding(2)]]
+var t_history: texture_2d<f32>;
+[[group(0), binding(3)]]
+var t_depth: texture_depth_2d;
+
+[[block]]
+struct TaaUniform {
+    inverse_view_proj: mat4x4<f32>;
+    previous_view_proj: mat4x4<f32>;
+    // weight of the current frame, 1 when there is no history
+    blend: f32;
+};
+[[group(1), binding(0)]]
+var<uniform> taa: TaaUniform;
+
+[[stage(fragment)]]
+fn fs_main(vertex: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let dimensions = textureDimensions(t_current);
+    let pixel = clamp(
+        vec2<i32>(vertex.tex_coords * vec2<f32>(dimensions)),
+        vec2<i32>(0),
+        dimensions - vec2<i32>(1),
+    );
+    let current = textureLoad(t_current, pixel, 0);
+
+    // bounds of the colors around the pixel, to reject the stale history
+    var low = current.rgb;
+    var high = current.rgb;
+    for (var y: i32 = -1; y <= 1; y = y + 1) {
+        for (var x: i32 = -1; x <= 1; x = x + 1) {
+            let neighbor = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), dimensions - vec2<i32>(1));
+            let color = textureLoad(t_current, neighbor, 0).rgb;
+            low = min(low, color);
+            high = max(high, color);
+        }
+    }
+
+    // position of the fragment on the previous frame
+    let depth = textureLoad(t_depth, pixel, 0);
+    let ndc = vec4<f32>(vertex.tex_coords.x * 2.0 - 1.0, 1.0 - vertex.tex_coords.y * 2.0, depth, 1.0);
+    let world = taa.inverse_view_proj * ndc;
+    let previous = taa.previous_view_proj * vec4<f32>(world.xyz / world.w, 1.0);
+    let previous_uv = vec2<f32>(
+        previous.x / previous.w * 0.5 + 0.5,
+        0.5 - previous.y / previous.w * 0.5,
+    );
+    let history = clamp(textureSample(t_history, s_input, previous_uv).rgb, low, high);
+
+    var blend = taa.blend;
+    if (any(previous_uv < vec2<f32>(0.0)) || any(previous_uv > vec2<f32>(1.0))) {
+        blend = 1.0;
+    }
+    return vec4<f32>(mix(history, current.rgb, blend), current.a);
+}
@@ -0,0 +1,25 @@
+// Unlit preset : the base color is output without lighting, the light bind group isn't bound.
+// A vertex & a fragment stage are appended, CAMERA_GROUP is replaced by the camera bind group index.
+
+[[block]]
+struct Camera {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+[[group(CAMERA_GROUP), binding(0)]]
+var<uniform> camera: Camera;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] tex_coords: vec2<f32>;
+    [[location(2)]] normal: vec3<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] tex_coords: vec2<f32>;
+    [[location(1)]] world_normal: vec3<f32>;
+    [[location(2)]] world_position: vec3<f32>;
+    // tint of the instance, white without instances
+    [[location(3)]] color: vec4<f32>;
+};
@@ -0,0 +1,7 @@
+// models without material are drawn grey, shaded by their normal facing the camera
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let view_dir = normalize(camera.view_pos.xyz - in.world_position);
+    let facing = abs(dot(normalize(in.world_normal), view_dir));
+    return vec4<f32>(vec3<f32>(0.7 * mix(0.35, 1.0, facing)), 1.0) * in.color;
+}
@@ -0,0 +1,30 @@
+struct InstanceInput {
+    [[location(4)]] color: vec4<f32>;
+    [[location(5)]] model_matrix_0: vec4<f32>;
+    [[location(6)]] model_matrix_1: vec4<f32>;
+    [[location(7)]] model_matrix_2: vec4<f32>;
+    [[location(8)]] model_matrix_3: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] vertex_index: u32,
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let world_position = model_matrix * vec4<f32>(morph_position(model.position, vertex_index), 1.0);
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.tex_coords = model.tex_coords;
+    // the instances are only moved, rotated & uniformly scaled
+    out.world_normal = (model_matrix * vec4<f32>(morph_normal(model.normal, vertex_index), 0.0)).xyz;
+    out.world_position = world_position.xyz;
+    out.color = instance.color;
+    return out;
+}
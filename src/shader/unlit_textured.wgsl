@@ -0,0 +1,20 @@
+[[group(0), binding(0)]]
+var t_diffuse: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_diffuse: sampler;
+
+[[block]]
+struct MaterialParams {
+    specular_color: vec3<f32>;
+    detail_scale: f32;
+    atlas_offset: vec2<f32>;
+    atlas_scale: vec2<f32>;
+};
+[[group(0), binding(4)]]
+var<uniform> material: MaterialParams;
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let tex_coords = material.atlas_offset + in.tex_coords * material.atlas_scale;
+    return textureSample(t_diffuse, s_diffuse, tex_coords) * in.color;
+}
@@ -0,0 +1,14 @@
+[[stage(vertex)]]
+fn vs_main(
+    [[builtin(vertex_index)]] vertex_index: u32,
+    model: VertexInput,
+) -> VertexOutput {
+    let position = morph_position(model.position, vertex_index);
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(position, 1.0);
+    out.tex_coords = model.tex_coords;
+    out.world_normal = morph_normal(model.normal, vertex_index);
+    out.world_position = position;
+    out.color = vec4<f32>(1.0);
+    return out;
+}
@@ -0,0 +1,53 @@
+// Water preset : CAMERA_GROUP is replaced by the camera bind group index, the other
+// capitalized names by the parameters of the object.
+
+[[block]]
+struct Camera {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+    time: f32;
+};
+[[group(CAMERA_GROUP), binding(0)]]
+var<uniform> camera: Camera;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(1)]] tex_coords: vec2<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_position: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    out.world_position = model.position;
+    return out;
+}
+
+// slope of a wave travelling along `direction`, its height is sin(frequency * distance - time)
+fn wave_slope(position: vec2<f32>, direction: vec2<f32>, frequency: f32, speed: f32) -> vec2<f32> {
+    let phase = dot(position, direction) * frequency - camera.time * WAVE_SPEED * speed;
+    return direction * frequency * cos(phase);
+}
+
+fn wave_normal(position: vec2<f32>) -> vec3<f32> {
+    let p = position * WAVE_SCALE;
+    let slope = wave_slope(p, vec2<f32>(1.0, 0.0), 1.0, 1.0)
+        + wave_slope(p, vec2<f32>(0.6, 0.8), 1.7, 1.3) * 0.5
+        + wave_slope(p, vec2<f32>(-0.8, 0.6), 2.9, 1.7) * 0.25;
+    return normalize(vec3<f32>(-slope.x * WAVE_HEIGHT, 1.0, -slope.y * WAVE_HEIGHT));
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let normal = wave_normal(in.world_position.xz);
+    let view_dir = normalize(camera.view_pos.xyz - in.world_position);
+    // Schlick's approximation with the reflectance of water
+    let fresnel = 0.02 + 0.98 * pow(1.0 - max(dot(normal, view_dir), 0.0), 5.0);
+    let color = mix(WATER_COLOR, SKY_COLOR, fresnel);
+    return vec4<f32>(color, mix(WATER_OPACITY, 1.0, fresnel));
+}
@@ -0,0 +1,44 @@
+// Wireframe drawn in a fill pipeline : the geometry is unindexed so every triangle
+// has its own vertices, the edges are found from the barycentric coordinates.
+// The vertex stage is appended, CAMERA_GROUP is replaced by the camera bind group index.
+
+[[block]]
+struct Camera {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+[[group(CAMERA_GROUP), binding(0)]]
+var<uniform> camera: Camera;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] barycentric: vec3<f32>;
+};
+
+// corner of the triangle from the vertex index of the unindexed geometry
+fn barycentric(vertex_index: u32) -> vec3<f32> {
+    let corner = vertex_index % 3u;
+    if (corner == 0u) {
+        return vec3<f32>(1.0, 0.0, 0.0);
+    }
+    if (corner == 1u) {
+        return vec3<f32>(0.0, 1.0, 0.0);
+    }
+    return vec3<f32>(0.0, 0.0, 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // edges keep about the same width in pixels whatever the triangle size
+    let width = fwidth(in.barycentric);
+    let edge = smoothStep(vec3<f32>(0.0), width * 1.5, in.barycentric);
+    let line = 1.0 - min(min(edge.x, edge.y), edge.z);
+    if (line < 0.01) {
+        discard;
+    }
+    return vec4<f32>(1.0, 1.0, 1.0, line);
+}
@@ -0,0 +1,10 @@
+[[stage(vertex)]]
+fn vs_main(
+    model: VertexInput,
+    [[builtin(vertex_index)]] vertex_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    out.barycentric = barycentric(vertex_index);
+    return out;
+}
@@ -0,0 +1,382 @@
+use crate::camera::OPENGL_TO_WGPU_MATRIX;
+use crate::light::{LightUniform, DIRECTIONAL_LIGHT, SPOT_LIGHT};
+use crate::renderer::{Aabb, InstanceRaw, ModelRenderer, ModelVertex, Vertex};
+use cgmath::*;
+use wgpu::util::DeviceExt;
+
+// Depth of the objects seen from the first light, drawn before the main pass and bound with the
+// light for the lit shaders to darken the fragments it doesn't reach. Directional lights see the
+// scene through an orthographic box around its bounds, point & spot lights through a perspective
+// from their position. Meshes are drawn as loaded, without their morph targets.
+
+pub const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+// constant & slope scaled depth bias of the objects drawn in the map, pushing them back against
+// the self-shadowing acne. Too large a bias detaches the shadows from their casters
+pub const DEFAULT_SHADOW_BIAS: (i32, f32) = (2, 2.0);
+
+// binding 2 of the light group
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowUniform {
+    pub light_view_proj: [[f32; 4]; 4],
+    // size of a texel of the map in texture coordinates, to offset the PCF samples
+    pub texel_size: f32,
+    // 0 without shadows, the shaders are then fully lit
+    pub enabled: u32,
+    pub _padding: [u32; 2],
+}
+
+/// Depth map of the first light, bound after the lights with the light view projection &
+/// a comparison sampler. Lit shaders multiply the intensity of the first light by the shadow
+/// factor :
+///
+/// ```wgsl
+/// [[block]]
+/// struct Shadow {
+///     light_view_proj: mat4x4<f32>;
+///     texel_size: f32;
+///     enabled: u32;
+/// };
+/// [[group(1), binding(2)]]
+/// var<uniform> shadow: Shadow;
+/// [[group(1), binding(3)]]
+/// var t_shadow: texture_depth_2d;
+/// [[group(1), binding(4)]]
+/// var s_shadow: sampler_comparison;
+///
+/// // 1 where the first light reaches the fragment, 0 in its shadow, averaged over 3x3 texels
+/// fn shadow_factor(world_position: vec3<f32>) -> f32 {
+///     if (shadow.enabled == 0u) {
+///         return 1.0;
+///     }
+///     let clip = shadow.light_view_proj * vec4<f32>(world_position, 1.0);
+///     let ndc = clip.xyz / clip.w;
+///     // beyond the far plane of the light
+///     if (ndc.z > 1.0) {
+///         return 1.0;
+///     }
+///     let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+///     var lit = 0.0;
+///     for (var x = -1; x <= 1; x = x + 1) {
+///         for (var y = -1; y <= 1; y = y + 1) {
+///             let offset = vec2<f32>(f32(x), f32(y)) * shadow.texel_size;
+///             lit = lit + textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, ndc.z);
+///         }
+///     }
+///     return lit / 9.0;
+/// }
+/// ```
+pub struct ShadowMap {
+    enabled: bool,
+    uniform: ShadowUniform,
+    uniform_buffer: wgpu::Buffer,
+    pub view: wgpu::TextureView,
+    // compares the depth of the fragments with the one of the map
+    pub sampler: wgpu::Sampler,
+    // the uniform alone, for the pass drawing the map
+    pass_bind_group: wgpu::BindGroup,
+    // kept to rebuild the pipelines when the bias changes
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    bias: (i32, f32),
+    // indexed by instanced, then by strip topology
+    pipelines: [[wgpu::RenderPipeline; 2]; 2],
+}
+
+impl ShadowMap {
+    // a 1x1 map cleared to the far plane is bound when `size` is None
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        size: Option<u32>,
+        bias: (i32, f32),
+    ) -> Self {
+        let enabled = size.is_some();
+        let size = size
+            .unwrap_or(1)
+            .clamp(1, device.limits().max_texture_dimension_2d);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Map"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // linear filters average the 4 nearest comparisons
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let uniform = ShadowUniform {
+            light_view_proj: Matrix4::identity().into(),
+            texel_size: 1.0 / size as f32,
+            enabled: enabled as u32,
+            _padding: [0; 2],
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shadow Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let pass_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("shadow_bind_group_layout"),
+        });
+        let pass_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &pass_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("shadow_bind_group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[&pass_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader/shadow.wgsl").into()),
+        });
+        let pipelines = create_pipelines(device, &pipeline_layout, &shader, bias);
+
+        let shadow_map = ShadowMap {
+            enabled,
+            uniform,
+            uniform_buffer,
+            view,
+            sampler,
+            pass_bind_group,
+            pipeline_layout,
+            shader,
+            bias,
+            pipelines,
+        };
+        // new textures are zeroed, which would shadow everything
+        if !enabled {
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Shadow Clear Encoder"),
+            });
+            drop(shadow_map.begin_pass(&mut encoder));
+            queue.submit(std::iter::once(encoder.finish()));
+        }
+        shadow_map
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // constant & slope scaled depth bias, the pipelines are rebuilt when it changes
+    pub fn set_bias(&mut self, device: &wgpu::Device, constant: i32, slope: f32) {
+        if self.bias == (constant, slope) {
+            return;
+        }
+        self.bias = (constant, slope);
+        self.pipelines = create_pipelines(device, &self.pipeline_layout, &self.shader, self.bias);
+    }
+
+    pub fn bias(&self) -> (i32, f32) {
+        self.bias
+    }
+
+    pub fn uniform_buffer(&self) -> &wgpu::Buffer {
+        &self.uniform_buffer
+    }
+
+    // fit the view of `light` to the bounds of the objects casting shadows
+    pub fn update(&mut self, queue: &wgpu::Queue, light: &LightUniform, bounds: Option<Aabb>) {
+        if !self.enabled {
+            return;
+        }
+        let bounds = match bounds {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        self.uniform.light_view_proj = light_view_proj(light, &bounds).into();
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    fn begin_pass<'a>(&'a self, encoder: &'a mut wgpu::CommandEncoder) -> wgpu::RenderPass<'a> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        })
+    }
+
+    // lines & points don't cast shadows
+    pub fn draw<'a>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        renderers: impl Iterator<Item = &'a ModelRenderer>,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        let mut render_pass = self.begin_pass(encoder);
+        render_pass.set_bind_group(0, &self.pass_bind_group, &[]);
+        for renderer in renderers.filter(|renderer| renderer.casts_shadows()) {
+            let instances = match renderer.instance_buffer.as_ref() {
+                Some(instance_buffer) => {
+                    render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+                    0..renderer.instance_count() as u32
+                }
+                None => 0..1,
+            };
+            let pipelines = &self.pipelines[renderer.instance_buffer.is_some() as usize];
+            for mesh in renderer.drawn_model().meshes.iter() {
+                let geometry = &mesh.geometry;
+                let pipeline = match geometry.topology {
+                    wgpu::PrimitiveTopology::TriangleList => &pipelines[0],
+                    wgpu::PrimitiveTopology::TriangleStrip => &pipelines[1],
+                    _ => continue,
+                };
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_vertex_buffer(0, geometry.vertex_buffer.slice(..));
+                render_pass
+                    .set_index_buffer(geometry.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                render_pass.draw_indexed(0..geometry.num_elements, 0, instances.clone());
+            }
+        }
+    }
+}
+
+// indexed by instanced, then by strip topology
+fn create_pipelines(
+    device: &wgpu::Device,
+    pipeline_layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    (constant, slope): (i32, f32),
+) -> [[wgpu::RenderPipeline; 2]; 2] {
+    let pipeline = |instanced: bool, topology: wgpu::PrimitiveTopology| {
+        let vertex_layouts = [ModelVertex::desc(), InstanceRaw::desc()];
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: if instanced { "vs_instanced" } else { "vs_main" },
+                buffers: &vertex_layouts[..1 + instanced as usize],
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                topology,
+                strip_index_format: topology.is_strip().then_some(wgpu::IndexFormat::Uint32),
+                front_face: wgpu::FrontFace::Ccw,
+                // both faces, the winding of the loaded models isn't always consistent
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                clamp_depth: false,
+                conservative: false,
+            },
+            // pushed back along the slopes against the self-shadowing acne
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: SHADOW_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState {
+                    constant,
+                    slope_scale: slope,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+        })
+    };
+    [false, true].map(|instanced| {
+        [
+            wgpu::PrimitiveTopology::TriangleList,
+            wgpu::PrimitiveTopology::TriangleStrip,
+        ]
+        .map(|topology| pipeline(instanced, topology))
+    })
+}
+
+// any up vector but the direction itself
+fn up_vector(direction: Vector3<f32>) -> Vector3<f32> {
+    if direction.y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    }
+}
+
+fn light_view_proj(light: &LightUniform, bounds: &Aabb) -> Matrix4<f32> {
+    let center = Point3::from(bounds.center());
+    let radius = bounds.radius().max(0.01);
+    let direction = Vector3::from(light.direction);
+
+    if light.kind == DIRECTIONAL_LIGHT {
+        // from outside of the bounds, so that every object is in front of the light
+        let eye = center - direction * radius * 2.0;
+        let view = Matrix4::look_to_rh(eye, direction, up_vector(direction));
+        let projection = ortho(-radius, radius, -radius, radius, radius, radius * 3.0);
+        return OPENGL_TO_WGPU_MATRIX * projection * view;
+    }
+
+    let eye = Point3::from(light.position);
+    let to_center = center - eye;
+    let distance = to_center.magnitude();
+    let (direction, fovy) = if light.kind == SPOT_LIGHT {
+        (
+            direction,
+            Rad(light.cos_outer.clamp(-1.0, 1.0).acos() * 2.0),
+        )
+    } else if distance > radius {
+        // point lights look at the bounds from outside of them
+        (to_center / distance, Rad((radius / distance).asin() * 2.0))
+    } else {
+        // the objects around a point light only shadow in front of it
+        let direction = if distance > f32::EPSILON {
+            to_center / distance
+        } else {
+            -Vector3::unit_y()
+        };
+        (direction, Rad(std::f32::consts::PI))
+    };
+    let fovy = Rad(fovy.0.clamp(0.01, 150.0_f32.to_radians()));
+    let near = (distance - radius).max(0.05);
+    let far = (distance + radius).max(near + 0.01);
+    let view = Matrix4::look_to_rh(eye, direction, up_vector(direction));
+    OPENGL_TO_WGPU_MATRIX * perspective(fovy, 1.0, near, far) * view
+}
@@ -0,0 +1,200 @@
+use crate::{fullscreen, texture};
+use cgmath::SquareMatrix;
+use wgpu::util::DeviceExt;
+
+// Temporal anti-aliasing of the opaque objects : the projection is jittered each frame,
+// the scene color is resolved with the history then copied back for the next passes.
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TaaUniform {
+    inverse_view_proj: [[f32; 4]; 4],
+    previous_view_proj: [[f32; 4]; 4],
+    blend: f32,
+    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
+    _padding: [u32; 3],
+}
+
+// weight of the current frame once the history is filled
+const BLEND: f32 = 0.1;
+// length of the jitter sequence
+const JITTER_SAMPLES: u32 = 8;
+
+// low discrepancy sequence in [0, 1)
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+pub struct Taa {
+    resolve: fullscreen::FullscreenPass,
+    taa_uniform: TaaUniform,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    history: texture::Texture,
+    output: texture::Texture,
+    bind_group: wgpu::BindGroup,
+    size: wgpu::Extent3d,
+    frame: u32,
+    // None until a frame is resolved, the history is then invalid
+    previous_view_proj: Option<cgmath::Matrix4<f32>>,
+}
+
+impl Taa {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        scene_color: &texture::Texture,
+        depth_view: &wgpu::TextureView,
+    ) -> Self {
+        let taa_uniform = TaaUniform {
+            inverse_view_proj: cgmath::Matrix4::identity().into(),
+            previous_view_proj: cgmath::Matrix4::identity().into(),
+            blend: 1.0,
+            _padding: [0; 3],
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("TAA Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[taa_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("taa_uniform_bind_group_layout"),
+        });
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &uniform_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("taa_uniform_bind_group"),
+        });
+
+        let resolve = fullscreen::FullscreenPass::with_layouts(
+            device,
+            include_str!("shader/taa.wgsl"),
+            &[
+                wgpu::TextureSampleType::Float { filterable: true },
+                wgpu::TextureSampleType::Float { filterable: true },
+                wgpu::TextureSampleType::Depth,
+            ],
+            &[&uniform_layout],
+            config.format,
+        );
+
+        let history = texture::Texture::create_render_target(device, config, "taa_history");
+        let output = texture::Texture::create_render_target(device, config, "taa_output");
+        let bind_group =
+            resolve.create_bind_group(device, &[&scene_color.view, &history.view, depth_view]);
+
+        Taa {
+            resolve,
+            taa_uniform,
+            uniform_buffer,
+            uniform_bind_group,
+            history,
+            output,
+            bind_group,
+            size: Taa::size(config),
+            frame: 0,
+            previous_view_proj: None,
+        }
+    }
+
+    fn size(config: &wgpu::SurfaceConfiguration) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        }
+    }
+
+    // the history is dropped with the old size
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        scene_color: &texture::Texture,
+        depth_view: &wgpu::TextureView,
+    ) {
+        self.history = texture::Texture::create_render_target(device, config, "taa_history");
+        self.output = texture::Texture::create_render_target(device, config, "taa_output");
+        self.bind_group = self
+            .resolve
+            .create_bind_group(device, &[&scene_color.view, &self.history.view, depth_view]);
+        self.size = Taa::size(config);
+        self.previous_view_proj = None;
+    }
+
+    // the next frame is resolved without the previous ones, e.g. after a camera cut
+    pub fn reset_history(&mut self) {
+        self.previous_view_proj = None;
+    }
+
+    // sub-pixel offset of the next frame in clip space
+    pub fn next_jitter(&mut self) -> [f32; 2] {
+        self.frame = (self.frame + 1) % JITTER_SAMPLES;
+        let x = halton(self.frame + 1, 2) - 0.5;
+        let y = halton(self.frame + 1, 3) - 0.5;
+        [
+            2.0 * x / self.size.width as f32,
+            2.0 * y / self.size.height as f32,
+        ]
+    }
+
+    // blend the scene color with the history and write the result in both
+    pub fn resolve(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        scene_color: &texture::Texture,
+        view_proj: cgmath::Matrix4<f32>,
+    ) {
+        let inverse_view_proj = view_proj.invert().unwrap_or_else(cgmath::Matrix4::identity);
+        self.taa_uniform.inverse_view_proj = inverse_view_proj.into();
+        self.taa_uniform.previous_view_proj = self.previous_view_proj.unwrap_or(view_proj).into();
+        self.taa_uniform.blend = if self.previous_view_proj.is_some() {
+            BLEND
+        } else {
+            1.0
+        };
+        self.previous_view_proj = Some(view_proj);
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.taa_uniform]),
+        );
+
+        self.resolve.draw_with_bind_groups(
+            encoder,
+            &self.output.view,
+            &[&self.bind_group, &self.uniform_bind_group],
+        );
+        encoder.copy_texture_to_texture(
+            self.output.texture.as_image_copy(),
+            self.history.texture.as_image_copy(),
+            self.size,
+        );
+        encoder.copy_texture_to_texture(
+            self.output.texture.as_image_copy(),
+            scene_color.texture.as_image_copy(),
+            self.size,
+        );
+    }
+}
@@ -1,11 +1,152 @@
 use anyhow::*;
 use image::GenericImageView;
+use std::collections::HashMap;
 use std::path::Path;
+use std::rc::Rc;
+
+// options used to create the sampler of a texture.
+// wgpu has no sampler LOD bias, shaders can pass one to `textureSampleBias`
+#[derive(Debug, Copy, Clone)]
+pub struct SamplerConfig {
+    pub address_mode: wgpu::AddressMode,
+    // levels of the textures sampled with this config, downscaled on the CPU,
+    // capped by the texture size, 1 disables mipmapping
+    pub mip_level_count: u32,
+    // range of mip levels that can be sampled, the sharpest level is 0
+    pub lod_min_clamp: f32,
+    pub lod_max_clamp: f32,
+    // samples taken along the slope of the textures seen at grazing angles, rounded down to
+    // a power of two up to MAX_ANISOTROPY, None or 1 for the default isotropic filtering
+    pub anisotropy_clamp: Option<u8>,
+}
+
+impl SamplerConfig {
+    // mip level count of the full chain, down to 1x1
+    pub const FULL_MIP_CHAIN: u32 = u32::MAX;
+}
+
+// highest anisotropy clamp accepted by wgpu
+pub const MAX_ANISOTROPY: u8 = 16;
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        SamplerConfig {
+            address_mode: wgpu::AddressMode::ClampToEdge,
+            mip_level_count: 1,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 32.0,
+            anisotropy_clamp: None,
+        }
+    }
+}
+
+// the clamps are compared by their bits so configs can key the sampler pool
+impl PartialEq for SamplerConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.address_mode == other.address_mode
+            && self.mip_level_count == other.mip_level_count
+            && self.lod_min_clamp.to_bits() == other.lod_min_clamp.to_bits()
+            && self.lod_max_clamp.to_bits() == other.lod_max_clamp.to_bits()
+            && self.anisotropy_clamp == other.anisotropy_clamp
+    }
+}
+
+impl Eq for SamplerConfig {}
+
+impl std::hash::Hash for SamplerConfig {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.address_mode.hash(state);
+        self.mip_level_count.hash(state);
+        self.lod_min_clamp.to_bits().hash(state);
+        self.lod_max_clamp.to_bits().hash(state);
+        self.anisotropy_clamp.hash(state);
+    }
+}
+
+impl SamplerConfig {
+    // the anisotropy is clamped to `max_anisotropy`, wgpu rejects the values that aren't powers
+    // of two and ignores them on the adapters without anisotropic filtering
+    fn create_sampler(&self, device: &wgpu::Device, max_anisotropy: u8) -> wgpu::Sampler {
+        let anisotropy_clamp = self
+            .anisotropy_clamp
+            .map(|clamp| clamp.min(max_anisotropy).min(MAX_ANISOTROPY))
+            .filter(|clamp| *clamp > 1)
+            .and_then(|clamp| std::num::NonZeroU8::new(1 << (7 - clamp.leading_zeros())));
+        // anisotropic filtering only applies to linear filters
+        let linear = self.mip_level_count > 1 || anisotropy_clamp.is_some();
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: self.address_mode,
+            address_mode_v: self.address_mode,
+            address_mode_w: self.address_mode,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: if linear {
+                wgpu::FilterMode::Linear
+            } else {
+                wgpu::FilterMode::Nearest
+            },
+            mipmap_filter: if linear {
+                wgpu::FilterMode::Linear
+            } else {
+                wgpu::FilterMode::Nearest
+            },
+            lod_min_clamp: self.lod_min_clamp,
+            lod_max_clamp: self.lod_max_clamp,
+            anisotropy_clamp,
+            ..Default::default()
+        })
+    }
+}
+
+// samplers shared by the textures having the same sampler config
+pub struct SamplerPool {
+    samplers: HashMap<SamplerConfig, Rc<wgpu::Sampler>>,
+    // anisotropy supported by the adapter, 1 without anisotropic filtering
+    max_anisotropy: u8,
+}
+
+impl Default for SamplerPool {
+    fn default() -> Self {
+        SamplerPool::with_max_anisotropy(MAX_ANISOTROPY)
+    }
+}
+
+impl SamplerPool {
+    pub fn with_max_anisotropy(max_anisotropy: u8) -> Self {
+        SamplerPool {
+            samplers: HashMap::new(),
+            max_anisotropy: max_anisotropy.max(1),
+        }
+    }
+
+    pub fn max_anisotropy(&self) -> u8 {
+        self.max_anisotropy
+    }
+
+    pub fn get(
+        &mut self,
+        device: &wgpu::Device,
+        sampler_config: &SamplerConfig,
+    ) -> Rc<wgpu::Sampler> {
+        self.samplers
+            .entry(*sampler_config)
+            .or_insert_with(|| Rc::new(sampler_config.create_sampler(device, self.max_anisotropy)))
+            .clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samplers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samplers.is_empty()
+    }
+}
 
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler,
+    // may be shared with other textures through a `SamplerPool`
+    pub sampler: Rc<wgpu::Sampler>,
 }
 
 impl Texture {
@@ -24,9 +165,86 @@ impl Texture {
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
         label: Option<&str>,
+    ) -> Result<Self> {
+        Self::from_image_with_sampler(device, queue, img, label, &SamplerConfig::default())
+    }
+
+    pub fn from_image_with_sampler(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        label: Option<&str>,
+        sampler_config: &SamplerConfig,
+    ) -> Result<Self> {
+        let sampler = Rc::new(sampler_config.create_sampler(device, MAX_ANISOTROPY));
+        Self::from_image_with_shared_sampler(
+            device,
+            queue,
+            img,
+            label,
+            sampler,
+            sampler_config.mip_level_count,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    // reuse the sampler of the pool matching the config
+    pub fn from_image_with_sampler_pool(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        label: Option<&str>,
+        sampler_config: &SamplerConfig,
+        sampler_pool: &mut SamplerPool,
+    ) -> Result<Self> {
+        let sampler = sampler_pool.get(device, sampler_config);
+        Self::from_image_with_shared_sampler(
+            device,
+            queue,
+            img,
+            label,
+            sampler,
+            sampler_config.mip_level_count,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    // for data stored in an image, e.g. a normal map, the values are sampled as they are
+    // instead of being converted from sRGB
+    pub fn from_linear_image_with_sampler_pool(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        label: Option<&str>,
+        sampler_config: &SamplerConfig,
+        sampler_pool: &mut SamplerPool,
+    ) -> Result<Self> {
+        let sampler = sampler_pool.get(device, sampler_config);
+        Self::from_image_with_shared_sampler(
+            device,
+            queue,
+            img,
+            label,
+            sampler,
+            sampler_config.mip_level_count,
+            wgpu::TextureFormat::Rgba8Unorm,
+        )
+    }
+
+    fn from_image_with_shared_sampler(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        label: Option<&str>,
+        sampler: Rc<wgpu::Sampler>,
+        mip_level_count: u32,
+        format: wgpu::TextureFormat,
     ) -> Result<Self> {
         let rgba = img.to_rgba8();
         let dimensions = img.dimensions();
+        // down to a 1x1 level at most
+        let max_levels = 32 - dimensions.0.max(dimensions.1).max(1).leading_zeros();
+        let mip_level_count = mip_level_count.clamp(1, max_levels);
 
         let size = wgpu::Extent3d {
             width: dimensions.0,
@@ -36,10 +254,10 @@ impl Texture {
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
         });
 
@@ -58,18 +276,37 @@ impl Texture {
             },
             size,
         );
+        for mip_level in 1..mip_level_count {
+            let width = (dimensions.0 >> mip_level).max(1);
+            let height = (dimensions.1 >> mip_level).max(1);
+            let level = image::imageops::resize(
+                &rgba,
+                width,
+                height,
+                image::imageops::FilterType::Triangle,
+            );
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                &level,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(4 * width),
+                    rows_per_image: std::num::NonZeroU32::new(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-
         Ok(Self {
             texture,
             view,
@@ -90,11 +327,187 @@ impl Texture {
         Self::from_image(device, queue, &img, label)
     }
 
+    // cube texture from six square images of the same size, in the +X, -X, +Y, -Y, +Z, -Z
+    // order, sampled with a direction through a `Cube` view
+    pub fn load_cubemap<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        paths: &[P; 6],
+        label: &str,
+    ) -> Result<Self> {
+        let mut faces = Vec::with_capacity(6);
+        for path in paths {
+            let path = path.as_ref();
+            let face = image::open(path)
+                .with_context(|| format!("cubemap face {:?}", path))?
+                .to_rgba8();
+            faces.push(face);
+        }
+        let side = faces[0].width();
+        if faces
+            .iter()
+            .any(|face| face.width() != side || face.height() != side)
+        {
+            bail!(
+                "the faces of cubemap {:?} aren't squares of the same size",
+                label
+            );
+        }
+
+        let size = wgpu::Extent3d {
+            width: side,
+            height: side,
+            depth_or_array_layers: 6,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        for (layer, face) in faces.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                },
+                face,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(4 * side),
+                    rows_per_image: std::num::NonZeroU32::new(side),
+                },
+                wgpu::Extent3d {
+                    depth_or_array_layers: 1,
+                    ..size
+                },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler: Rc::new(sampler),
+        })
+    }
+
+    // color texture with the size and format of the surface, to render offscreen
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        label: &str,
+    ) -> Self {
+        Self::create_render_target_with_format(device, config, config.format, label)
+    }
+
+    // render target of the surface size in another format than the surface one
+    pub fn create_render_target_with_format(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler: Rc::new(sampler),
+        }
+    }
+
+    // color target in the surface format with `sample_count` samples per pixel,
+    // resolved into a single sampled texture at the end of the passes
+    pub fn create_multisampled_target(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // never sampled, kept for the shape of `Texture`
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+
+        Self {
+            texture,
+            view,
+            sampler: Rc::new(sampler),
+        }
+    }
+
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    // used when objects need a stencil buffer, it can't be copied
+    pub const DEPTH_STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
 
     pub fn create_depth_texture(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
         label: &str,
     ) -> Self {
         let size = wgpu::Extent3d {
@@ -106,10 +519,19 @@ impl Texture {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            format,
+            // sampled or copied by custom passes, a multisampled depth is only rendered to
+            usage: if sample_count > 1 {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            } else if format == Self::DEPTH_STENCIL_FORMAT {
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+            } else {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_SRC
+            },
         };
         let texture = device.create_texture(&desc);
 
@@ -130,7 +552,7 @@ impl Texture {
         Self {
             texture,
             view,
-            sampler,
+            sampler: Rc::new(sampler),
         }
     }
 }
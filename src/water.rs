@@ -0,0 +1,61 @@
+// Water preset : a plane drawn with animated normals, a sum of waves travelling in
+// different directions moved by `camera.time`. The color goes from the water color looking
+// straight down to the sky color at grazing angles (fresnel), there is no true reflection.
+// The parameters are written in the shader as constants.
+
+#[derive(Debug, Copy, Clone)]
+pub struct WaterParams {
+    // height of the plane
+    pub height: f32,
+    // color looking straight down & reflected at grazing angles
+    pub color: [f32; 3],
+    pub sky_color: [f32; 3],
+    // alpha looking straight down, the water turns opaque at grazing angles
+    pub opacity: f32,
+    // waves per world unit, radians per second & slope of the waves
+    pub wave_scale: f32,
+    pub wave_speed: f32,
+    pub wave_height: f32,
+}
+
+impl Default for WaterParams {
+    fn default() -> Self {
+        WaterParams {
+            height: 0.0,
+            color: [0.0, 0.2, 0.3],
+            sky_color: [0.6, 0.75, 0.9],
+            opacity: 0.7,
+            wave_scale: 1.5,
+            wave_speed: 1.0,
+            wave_height: 0.15,
+        }
+    }
+}
+
+// cells along each side of the plane, the normals are computed per pixel
+pub(crate) const SUBDIVISIONS: u32 = 16;
+
+// WGSL float literal, non finite values are replaced by 0
+fn float(value: f32) -> String {
+    format!("{:?}", if value.is_finite() { value } else { 0.0 })
+}
+
+fn vec3(value: [f32; 3]) -> String {
+    format!(
+        "vec3<f32>({}, {}, {})",
+        float(value[0]),
+        float(value[1]),
+        float(value[2])
+    )
+}
+
+// the water shader of `params`, CAMERA_GROUP is replaced by the caller
+pub(crate) fn shader(params: &WaterParams) -> String {
+    include_str!("shader/water.wgsl")
+        .replace("WATER_COLOR", &vec3(params.color))
+        .replace("SKY_COLOR", &vec3(params.sky_color))
+        .replace("WATER_OPACITY", &float(params.opacity.clamp(0.0, 1.0)))
+        .replace("WAVE_SCALE", &float(params.wave_scale))
+        .replace("WAVE_SPEED", &float(params.wave_speed))
+        .replace("WAVE_HEIGHT", &float(params.wave_height))
+}
@@ -0,0 +1,236 @@
+// Frames rendered headless & checked pixel by pixel. They need an adapter, a GPU or a software
+// rasterizer, the tests drawing frames are ignored by default : `cargo test -- --ignored`.
+
+const SIZE: u32 = 64;
+
+// colors the faces by their normal, +Z is blue & -Z is yellow
+const NORMAL_SHADER: &str = r#"
+[[block]]
+struct Camera {
+    view_pos: vec4<f32>;
+    view_proj: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> camera: Camera;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(2)]] normal: vec3<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] normal: vec3<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    out.normal = model.normal;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(normalize(in.normal) * 0.5 + 0.5, 1.0);
+}
+"#;
+
+// cube of side 2 around the origin whose faces are wound clockwise seen from outside
+const CW_CUBE: &str = "
+v -1 -1 -1
+v 1 -1 -1
+v 1 1 -1
+v -1 1 -1
+v -1 -1 1
+v 1 -1 1
+v 1 1 1
+v -1 1 1
+vn 0 0 1
+vn 0 0 -1
+vn 1 0 0
+vn -1 0 0
+vn 0 1 0
+vn 0 -1 0
+f 8//1 7//1 6//1 5//1
+f 3//2 4//2 1//2 2//2
+f 7//3 3//3 2//3 6//3
+f 4//4 8//4 5//4 1//4
+f 4//5 3//5 7//5 8//5
+f 5//6 6//6 2//6 1//6
+";
+
+// single frame seen from +Z
+fn render(lens: lens::Lens) -> image::RgbaImage {
+    let mut frames = lens
+        .with_camera_origin([0.0, 0.0, 4.0], [0.0, 0.0, 0.0])
+        .run_headless(SIZE, SIZE, 1)
+        .expect("the frame can't be rendered without adapter");
+    frames.pop().unwrap()
+}
+
+fn center(frame: &image::RgbaImage) -> [u8; 4] {
+    frame.get_pixel(SIZE / 2, SIZE / 2).0
+}
+
+fn cw_cube() -> lens::Object {
+    lens::Object::load_from_reader(CW_CUBE.as_bytes(), |_| None).unwrap()
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn cw_cube_is_drawn_with_cw_front_face() {
+    let mut lens = lens::Lens::new();
+    lens.add_object(
+        lens::LensObject::new(cw_cube(), NORMAL_SHADER).with_front_face(wgpu::FrontFace::Cw),
+    );
+    let frame = render(lens);
+    // the face toward the camera
+    let [_, _, blue, _] = center(&frame);
+    assert!(blue > 200, "center pixel {:?}", center(&frame));
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn cw_cube_is_culled_with_default_front_face() {
+    let mut lens = lens::Lens::new();
+    lens.add_object(lens::LensObject::new(cw_cube(), NORMAL_SHADER));
+    let frame = render(lens);
+    // the faces toward the camera are culled as back faces, the far one shows through
+    let [_, _, blue, _] = center(&frame);
+    assert!(blue < 64, "center pixel {:?}", center(&frame));
+}
+
+// cube seen from +Z, its front face is red, its back face green & its other faces have no
+// material
+const TWO_MATERIAL_CUBE: &str = "
+mtllib cube.mtl
+v -1 -1 -1
+v 1 -1 -1
+v 1 1 -1
+v -1 1 -1
+v -1 -1 1
+v 1 -1 1
+v 1 1 1
+v -1 1 1
+vn 0 0 1
+vn 0 0 -1
+vn 1 0 0
+vn -1 0 0
+vn 0 1 0
+vn 0 -1 0
+f 3//3 7//3 6//3 2//3
+f 8//4 4//4 1//4 5//4
+f 8//5 7//5 3//5 4//5
+f 1//6 2//6 6//6 5//6
+usemtl red
+f 5//1 6//1 7//1 8//1
+usemtl green
+f 2//2 1//2 4//2 3//2
+";
+
+const TWO_MATERIAL_MTL: &str = "
+newmtl red
+map_Kd red.png
+newmtl green
+map_Kd green.png
+";
+
+fn png(color: [u8; 4]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(color)))
+        .write_to(&mut bytes, image::ImageOutputFormat::Png)
+        .unwrap();
+    bytes
+}
+
+fn two_material_cube() -> lens::Object {
+    lens::Object::load_from_reader(TWO_MATERIAL_CUBE.as_bytes(), |name| match name {
+        "cube.mtl" => Some(TWO_MATERIAL_MTL.as_bytes().to_vec()),
+        "red.png" => Some(png([255, 0, 0, 255])),
+        "green.png" => Some(png([0, 255, 0, 255])),
+        _ => None,
+    })
+    .unwrap()
+}
+
+// index of the model drawn with `material_id`
+fn model_with_material(object: &lens::Object, material_id: Option<usize>) -> usize {
+    object
+        .models
+        .iter()
+        .position(|model| model.mesh.material_id == material_id)
+        .unwrap()
+}
+
+#[test]
+fn two_material_cube_is_split_by_material() {
+    let object = two_material_cube();
+    assert_eq!(object.textures.as_ref().map(Vec::len), Some(2));
+    let mut material_ids: Vec<_> = object
+        .models
+        .iter()
+        .map(|model| model.mesh.material_id)
+        .collect();
+    material_ids.sort();
+    assert_eq!(material_ids, [None, Some(0), Some(1)]);
+    // the faces of each material stay with it, 2 triangles for the red & green faces
+    let red = &object.models[model_with_material(&object, Some(0))];
+    assert_eq!(red.mesh.indices.len(), 6);
+    assert!(red
+        .mesh
+        .normals
+        .chunks_exact(3)
+        .all(|n| n == [0.0, 0.0, 1.0]));
+}
+
+#[test]
+fn material_without_map_kd_is_its_diffuse_color() {
+    let object = lens::Object::load_from_reader(TWO_MATERIAL_CUBE.as_bytes(), |name| match name {
+        "cube.mtl" => Some(b"newmtl red\nKd 1 0 0\nnewmtl green\nKd 0 1 0\n".to_vec()),
+        _ => None,
+    })
+    .unwrap();
+    let textures = object.textures.unwrap();
+    assert_eq!(
+        textures[0].diffuse.to_rgba8(),
+        image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 0, 0, 255]))
+    );
+    assert_eq!(
+        textures[1].diffuse.to_rgba8(),
+        image::RgbaImage::from_pixel(1, 1, image::Rgba([0, 255, 0, 255]))
+    );
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn two_material_cube_binds_the_material_of_each_mesh() {
+    let mut lens = lens::Lens::new();
+    lens.add_object(lens::LensObject::new(two_material_cube(), "").unlit());
+    let frame = render(lens);
+    // the red front face
+    assert_eq!(
+        &center(&frame)[..3],
+        [255, 0, 0],
+        "center pixel {:?}",
+        center(&frame)
+    );
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn invalid_material_id_falls_back_to_white() {
+    let mut object = two_material_cube();
+    let red = model_with_material(&object, Some(0));
+    object.models[red].mesh.material_id = Some(7);
+    let mut lens = lens::Lens::new();
+    lens.add_object(lens::LensObject::new(object, "").unlit());
+    let frame = render(lens);
+    assert_eq!(
+        &center(&frame)[..3],
+        [255, 255, 255],
+        "center pixel {:?}",
+        center(&frame)
+    );
+}